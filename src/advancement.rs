@@ -0,0 +1,140 @@
+//! Spending Good Karma between runs: what raising an attribute, a skill or a
+//! spell costs, and the ledger of what's been bought.
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use character::AttrError;
+
+/// What Karma can improve.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Advance {
+    Attribute,
+    Skill,
+    Knowledge,
+    Spell,
+}
+
+impl FromStr for Advance {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Advance, ()> {
+        match s.to_lowercase().as_str() {
+            "attribute" => Ok(Advance::Attribute),
+            "skill" => Ok(Advance::Skill),
+            "knowledge" => Ok(Advance::Knowledge),
+            "spell" => Ok(Advance::Spell),
+            _ => Err(()),
+        }
+    }
+}
+
+/// An attribute raised to `new` costs twice the new rating.
+pub fn attribute_cost(new: i32) -> i32 {
+    2 * new
+}
+
+/// An active skill costs one and a half times the new rating up to its
+/// linked attribute, twice that up to double the attribute, and two and a
+/// half times beyond. Halves round up.
+pub fn active_skill_cost(new: i32, linked: i32) -> i32 {
+    if new <= linked {
+        (3 * new + 1) / 2
+    } else if new <= 2 * linked {
+        2 * new
+    } else {
+        (5 * new + 1) / 2
+    }
+}
+
+/// Knowledge skills run cheaper: the new rating up to Intelligence, one and
+/// a half times up to double it, and twice beyond.
+pub fn knowledge_skill_cost(new: i32, intelligence: i32) -> i32 {
+    if new <= intelligence {
+        new
+    } else if new <= 2 * intelligence {
+        (3 * new + 1) / 2
+    } else {
+        2 * new
+    }
+}
+
+/// Learning a spell, or relearning it a Force higher, costs the new Force.
+pub fn spell_cost(force: i32) -> i32 {
+    force
+}
+
+/// One line of a character's karma ledger.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Purchase {
+    pub kind: Advance,
+    pub name: String,
+    pub rating: i32,
+    pub cost: i32,
+    /// Seconds since the Unix epoch.
+    pub at: u64,
+}
+
+impl Purchase {
+    /// Records a purchase made just now.
+    pub fn now(kind: Advance, name: &str, rating: i32, cost: i32) -> Purchase {
+        let at = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        Purchase { kind, name: name.to_owned(), rating, cost, at }
+    }
+}
+
+impl fmt::Display for Purchase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            Advance::Attribute => "",
+            Advance::Skill => " skill",
+            Advance::Knowledge => " knowledge",
+            Advance::Spell => " spell",
+        };
+        write!(f, "{}{} to {} for {} karma", self.name, kind, self.rating, self.cost)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdvanceError {
+    /// Carries the cost and the Good Karma on hand.
+    NotEnoughKarma(i32, i32),
+    NoSuchAttribute(String),
+    Attribute(AttrError),
+    Unawakened,
+}
+
+impl fmt::Display for AdvanceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AdvanceError::NotEnoughKarma(cost, karma) =>
+                write!(f, "that costs {} karma, and there's only {}", cost, karma),
+            AdvanceError::NoSuchAttribute(ref a) =>
+                write!(f, "no attribute named '{}'", a),
+            AdvanceError::Attribute(ref e) =>
+                write!(f, "{}", e),
+            AdvanceError::Unawakened =>
+                write!(f, "only the Awakened can learn spells"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use advancement::{active_skill_cost, knowledge_skill_cost, Advance, Purchase};
+
+    #[test]
+    fn test_costs() {
+        assert_eq!(active_skill_cost(3, 4), 5);
+        assert_eq!(active_skill_cost(4, 4), 6);
+        assert_eq!(active_skill_cost(5, 4), 10);
+        assert_eq!(active_skill_cost(5, 2), 13);
+        assert_eq!(knowledge_skill_cost(3, 3), 3);
+        assert_eq!(knowledge_skill_cost(5, 3), 8);
+        assert_eq!(knowledge_skill_cost(7, 3), 14);
+
+        let bought = Purchase::now(Advance::Skill, "pistols", 4, 6);
+        assert!(bought.at > 0);
+        assert_eq!(bought.to_string(), "pistols skill to 4 for 6 karma");
+    }
+}
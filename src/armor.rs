@@ -0,0 +1,47 @@
+//! Worn armor. Each piece has a Ballistic rating against bullets and an
+//! Impact rating against everything blunter.
+use std::fmt;
+
+/// Which of an armor's ratings an attack has to get through.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ArmorRating {
+    Ballistic,
+    Impact,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Armor {
+    pub name: String,
+    pub ballistic: i32,
+    pub impact: i32,
+}
+
+impl Armor {
+    pub fn new(name: &str, ballistic: i32, impact: i32) -> Armor {
+        Armor { name: name.to_owned(), ballistic, impact }
+    }
+
+    pub fn rating(&self, rating: ArmorRating) -> i32 {
+        match rating {
+            ArmorRating::Ballistic => self.ballistic,
+            ArmorRating::Impact => self.impact,
+        }
+    }
+}
+
+impl fmt::Display for Armor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}/{}", self.name, self.ballistic, self.impact)
+    }
+}
+
+/// The armor everybody knows about.
+pub fn wardrobe() -> Vec<Armor> {
+    vec![
+        Armor::new("armor clothing", 3, 0),
+        Armor::new("armor vest", 2, 1),
+        Armor::new("lined coat", 4, 2),
+        Armor::new("armor jacket", 5, 3),
+        Armor::new("riot gear", 6, 4),
+    ]
+}
@@ -0,0 +1,341 @@
+//! Campaign files: the party, their NPCs, the spells the table knows about
+//! and a log of what's been rolled, saved as TOML between sessions.
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de;
+use toml;
+
+use character::Character;
+use common::{Attribute, DamageLevel, TargetNumber};
+use grimoire::Grimoire;
+use magic::{Drain, KnownSpell};
+
+/// Bump this whenever a change to the file format needs more than new
+/// fields with defaults, and teach `migrate` how to bring old saves along.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// One line of the roll log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoggedRoll {
+    pub who: Option<String>,
+    pub what: String,
+    pub outcome: String,
+}
+
+// TOML can't write an empty list once a table has been written, so empty
+// lists are left out and come back as their defaults.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Campaign {
+    pub version: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub party: Vec<Character>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub npcs: Vec<Character>,
+    /// The core grimoire with the campaign's own spells laid over it. Only
+    /// the spells core lacks or has differently are saved.
+    #[serde(serialize_with = "save_spells", deserialize_with = "load_spells",
+            skip_serializing_if = "only_core")]
+    pub spells: Grimoire,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub roll_log: Vec<LoggedRoll>,
+}
+
+impl Default for Campaign {
+    fn default() -> Campaign {
+        Campaign {
+            version: FORMAT_VERSION,
+            party: Vec::new(),
+            npcs: Vec::new(),
+            spells: Grimoire::core(),
+            roll_log: Vec::new(),
+        }
+    }
+}
+
+fn only_core(spells: &Grimoire) -> bool {
+    spells.changes_from(&Grimoire::core()).is_empty()
+}
+
+fn save_spells<S: Serializer>(spells: &Grimoire, s: S) -> Result<S::Ok, S::Error> {
+    spells.changes_from(&Grimoire::core()).serialize(s)
+}
+
+fn load_spells<'de, D: Deserializer<'de>>(d: D) -> Result<Grimoire, D::Error> {
+    let core = Grimoire::core();
+    let mut spells = core.clone();
+    for saved in Vec::<SavedSpell>::deserialize(d)? {
+        spells.add(match saved {
+            SavedSpell::Current(spell) => spell,
+            SavedSpell::Legacy(old) => old.upgrade(&core).map_err(de::Error::custom)?,
+        });
+    }
+    Ok(spells)
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SavedSpell {
+    Current(KnownSpell),
+    Legacy(LegacySpell),
+}
+
+/// A spell as saves listed it before the grimoire file: just a name, drain
+/// and target.
+#[derive(Deserialize)]
+struct LegacySpell {
+    name: String,
+    drain_level: DamageLevel,
+    drain_modifier: i32,
+    target: LegacyTarget,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyTarget {
+    Fixed(TargetNumber),
+    Resisted(Attribute),
+}
+
+impl LegacySpell {
+    /// Keeps the saved drain and target, and takes what old saves didn't
+    /// record from the core spell of the same name.
+    fn upgrade(self, core: &Grimoire) -> Result<KnownSpell, String> {
+        let mut spell = core.find(&self.name).cloned().ok_or_else(|| {
+            format!("spell '{}' predates the grimoire file and isn't in the core grimoire",
+                    self.name)
+        })?;
+        let drain = Drain::new(self.drain_modifier, self.drain_level);
+        match (&mut spell, self.target) {
+            (&mut KnownSpell::Fixed(ref mut s), LegacyTarget::Fixed(tn)) => {
+                s.drain = drain;
+                s.target = tn;
+            },
+            (&mut KnownSpell::Resisted(ref mut s), LegacyTarget::Resisted(attr)) => {
+                s.drain = drain;
+                s.target = attr;
+            },
+            _ => return Err(format!("spell '{}' is resisted differently in the core grimoire",
+                                    self.name)),
+        }
+        Ok(spell)
+    }
+}
+
+#[derive(Debug)]
+pub enum CampaignError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Write(toml::ser::Error),
+    TooNew(u32),
+}
+
+impl fmt::Display for CampaignError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CampaignError::Io(ref e) => write!(f, "{}", e),
+            CampaignError::Parse(ref e) => write!(f, "bad campaign file: {}", e),
+            CampaignError::Write(ref e) => write!(f, "could not write campaign: {}", e),
+            CampaignError::TooNew(v) =>
+                write!(f, "campaign file is version {}, but this shadowfun only \
+                           understands up to version {}", v, FORMAT_VERSION),
+        }
+    }
+}
+
+impl Error for CampaignError {}
+
+impl From<io::Error> for CampaignError {
+    fn from(e: io::Error) -> CampaignError {
+        CampaignError::Io(e)
+    }
+}
+
+impl Campaign {
+    pub fn new() -> Campaign {
+        Campaign::default()
+    }
+
+    pub fn from_toml(text: &str) -> Result<Campaign, CampaignError> {
+        let campaign: Campaign = toml::from_str(text).map_err(CampaignError::Parse)?;
+        campaign.migrate()
+    }
+
+    pub fn to_toml(&self) -> Result<String, CampaignError> {
+        toml::to_string(self).map_err(CampaignError::Write)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Campaign, CampaignError> {
+        Campaign::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Writes the campaign next to `path` first and then moves it into
+    /// place, so a crash mid-save never leaves a half-written file behind.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CampaignError> {
+        let path = path.as_ref();
+        let text = self.to_toml()?;
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        {
+            let mut file = fs::File::create(&tmp)?;
+            file.write_all(text.as_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn migrate(mut self) -> Result<Campaign, CampaignError> {
+        if self.version > FORMAT_VERSION {
+            return Err(CampaignError::TooNew(self.version));
+        }
+        // Nothing has needed more than field defaults yet; spells saved before
+        // the grimoire file are brought along as they load.
+        self.version = FORMAT_VERSION;
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use campaign::{Campaign, CampaignError, LoggedRoll, FORMAT_VERSION};
+    use character::{Character, Race};
+    use common::{Attribute, HasAttrs};
+    use grimoire::Grimoire;
+    use magic::{Category, KnownSpell};
+
+    #[test]
+    fn test_round_trip() {
+        let mut campaign = Campaign::new();
+        let mut jill = Character::new("jill", Race::Ork);
+        jill.set_attr(Attribute::Willpower, 3).unwrap();
+        jill.learn_skill("sorcery");
+        campaign.party.push(jill);
+        campaign.npcs.push(Character::new("frank", Race::Elf));
+        campaign.roll_log.push(LoggedRoll {
+            who: Some("jill".to_owned()),
+            what: "test sorcery 4".to_owned(),
+            outcome: "[5] vs TN 4 \u{2192} 1 success".to_owned(),
+        });
+        assert!(!campaign.to_toml().unwrap().contains("[[spells]]"));
+
+        let house = Grimoire::from_toml(r#"
+            [[spell]]
+            name = "wreck"
+            category = "Manipulation"
+            type = "Physical"
+            duration = "Instant"
+            range = "Touch"
+            drain = "(F/2)+3 D"
+            target = 6
+
+            [[spell]]
+            name = "oxygenate"
+            category = "Health"
+            type = "Mana"
+            duration = "Sustained"
+            range = "Touch"
+            drain = "(F/2)+1 L"
+            target = 4
+        "#).unwrap();
+        for spell in &house.spells {
+            campaign.spells.add(spell.clone());
+        }
+
+        let text = campaign.to_toml().unwrap();
+        assert_eq!(text.matches("[[spells]]").count(), 2);
+        let loaded = Campaign::from_toml(&text).unwrap();
+        assert_eq!(loaded.party.len(), 1);
+        assert_eq!(loaded.party[0].attr(Attribute::Willpower), 3);
+        assert_eq!(loaded.party[0].skill("sorcery"), 1);
+        assert_eq!(loaded.npcs[0].name(), "frank");
+        assert!(loaded.spells.find("manabolt").is_some());
+        assert_eq!(loaded.spells.find("wreck"), house.find("wreck"));
+        assert_eq!(loaded.spells.find("oxygenate"), house.find("oxygenate"));
+        assert_eq!(loaded.spells.spells.len(), Grimoire::core().spells.len() + 1);
+        assert_eq!(loaded.roll_log, campaign.roll_log);
+    }
+
+    #[test]
+    fn test_old_saves_load() {
+        // A save from before characters had most of their fields.
+        let old = r#"
+            version = 1
+
+            [[party]]
+            name = "jill"
+            race = "Ork"
+            willpower = 3
+
+            [[spells]]
+            name = "oxygenate"
+            drain_level = "Light"
+            drain_modifier = 1
+            target = 5
+        "#;
+        let campaign = Campaign::from_toml(old).unwrap();
+        assert_eq!(campaign.version, FORMAT_VERSION);
+        assert_eq!(campaign.party[0].name(), "jill");
+        assert_eq!(campaign.party[0].attr(Attribute::Willpower), 3);
+        assert_eq!(campaign.party[0].skill("sorcery"), 0);
+        assert!(campaign.npcs.is_empty());
+        // Old spells keep their drain and target, and take the rest from core.
+        match *campaign.spells.find("oxygenate").unwrap() {
+            KnownSpell::Fixed(ref s) => {
+                assert_eq!(s.drain.to_string(), "(F/2)+1 L");
+                assert_eq!(s.target, 5);
+                assert_eq!(s.category, Category::Health);
+            },
+            ref other => panic!("expected a fixed spell, got {:?}", other),
+        }
+        assert!(campaign.spells.find("manabolt").is_some());
+
+        // ...but there's nothing to fill in one core doesn't know.
+        let unknown = r#"
+            [[spells]]
+            name = "wreck"
+            drain_level = "Deadly"
+            drain_modifier = 3
+            target = 6
+        "#;
+        match Campaign::from_toml(unknown) {
+            Err(e @ CampaignError::Parse(_)) =>
+                assert!(e.to_string().contains("isn't in the core grimoire")),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_newer_saves_are_refused() {
+        match Campaign::from_toml("version = 99") {
+            Err(CampaignError::TooNew(99)) => (),
+            other => panic!("expected TooNew, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = env::temp_dir().join(format!("shadowfun-test-{}.toml",
+                                                 ::std::process::id()));
+        let mut campaign = Campaign::new();
+        campaign.party.push(Character::new("jill", Race::Ork));
+        campaign.save(&path).unwrap();
+        campaign.party.push(Character::new("frank", Race::Elf));
+        campaign.save(&path).unwrap();
+
+        let loaded = Campaign::load(&path).unwrap();
+        assert_eq!(loaded.party.len(), 2);
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        assert!(fs::metadata(&tmp).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,14 +1,36 @@
 use std::collections::HashMap;
-use std::cmp::max;
+use std::cmp::{max, min};
+use std::fmt;
+use std::rc::Rc;
+use std::str::FromStr;
+use advancement::{active_skill_cost, attribute_cost, knowledge_skill_cost, spell_cost, Advance,
+                  AdvanceError, Purchase};
+use armor::{Armor, ArmorRating};
+use combat::Initiative;
 use common;
 use common::{HasAttrs, Attribute, DamageType, DamageLevel, TargetNumber};
-use dice::{RollResult, Roller};
-use magic::{SpellName, ForceLevel, Spell, SpellTargetNumber, SpellResult};
+use cyberware::{format_essence, CyberError, Essence, Implant, FULL_ESSENCE};
+use dice::{KarmaSpend, RollResult, Roller};
+use events::{Event, EventSink, StdoutSink};
+use grimoire::Grimoire;
+use pools::{Pool, PoolError, PoolState, PoolTest};
+use skills;
+use skills::{Defaulting, SkillError, Specialty};
+use magic::{Duration, KnownSpell, SpellName, ForceLevel, Spell, SpellTargetNumber, SpellResult,
+            SustainedSpell};
+use weapons::DamageCode;
 
-pub type Skill = &'static str;
+/// What each point of force above the learned force adds to the sorcery
+/// target number.
+pub const OVERCAST_PENALTY: TargetNumber = 2;
+
+/// What each spell a character is sustaining adds to their target numbers.
+pub const SUSTAINING_PENALTY: TargetNumber = 2;
+
+pub type Skill = String;
 pub type SkillLevel = i32;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Race {
     Human,
     Ork,
@@ -17,105 +39,568 @@ pub enum Race {
     Troll,
 }
 
-#[derive(Debug)]
-pub struct Character<'a, T:Roller + 'a> {
-    name: &'static str,
+impl FromStr for Race {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Race, ()> {
+        match s.to_lowercase().as_str() {
+            "human" => Ok(Race::Human),
+            "ork" => Ok(Race::Ork),
+            "elf" => Ok(Race::Elf),
+            "dwarf" => Ok(Race::Dwarf),
+            "troll" => Ok(Race::Troll),
+            _ => Err(()),
+        }
+    }
+}
+
+/// What a metatype can see in the dark.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Vision {
+    Normal,
+    LowLight,
+    Thermographic,
+}
+
+impl Race {
+    /// How much the metatype adds to (or takes from) an attribute.
+    pub fn modifier(self, attr: Attribute) -> i32 {
+        use common::Attribute::*;
+        match (self, attr) {
+            (Race::Human, _) => 0,
+            (Race::Dwarf, Body) => 1,
+            (Race::Dwarf, Strength) => 2,
+            (Race::Dwarf, Willpower) => 1,
+            (Race::Elf, Quickness) => 1,
+            (Race::Elf, Charisma) => 2,
+            (Race::Ork, Body) => 3,
+            (Race::Ork, Strength) => 2,
+            (Race::Ork, Charisma) => -1,
+            (Race::Ork, Intelligence) => -1,
+            (Race::Troll, Body) => 5,
+            (Race::Troll, Quickness) => -1,
+            (Race::Troll, Strength) => 4,
+            (Race::Troll, Intelligence) => -2,
+            (Race::Troll, Charisma) => -2,
+            _ => 0,
+        }
+    }
+
+    /// The highest an attribute can be raised naturally.
+    pub fn limit(self, attr: Attribute) -> i32 {
+        6 + self.modifier(attr)
+    }
+
+    /// The highest an attribute can go at all, augmentations included: half
+    /// again the racial limit, rounded up.
+    pub fn maximum(self, attr: Attribute) -> i32 {
+        (self.limit(attr) * 3 + 1) / 2
+    }
+
+    pub fn vision(self) -> Vision {
+        match self {
+            Race::Human => Vision::Normal,
+            Race::Elf | Race::Ork => Vision::LowLight,
+            Race::Dwarf | Race::Troll => Vision::Thermographic,
+        }
+    }
+
+    /// Trolls' skin counts as armor against every attack.
+    pub fn dermal_armor(self) -> i32 {
+        match self {
+            Race::Troll => 1,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AttrError {
+    /// Higher than the character's metatype allows; carries the limit.
+    OverRacialLimit(Race, Attribute, i32),
+}
+
+impl fmt::Display for AttrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AttrError::OverRacialLimit(race, attr, limit) =>
+                write!(f, "{:?} {:?} can't go above {}", race, attr, limit),
+        }
+    }
+}
+
+/// Armor can't make damage any easier to resist than this.
+pub const MIN_RESISTANCE_TN: TargetNumber = 2;
+
+/// A damage resistance test and whatever got through it.
+#[derive(Debug, PartialEq)]
+pub struct Resistance {
+    pub roll: RollResult,
+    pub damage: Option<DamageLevel>,
+}
+
+// Missing fields fall back to their defaults so that characters saved before
+// a field existed still load.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Character {
+    name: String,
     body: i32,
     race: Race,
     intelligence: i32,
     strength: i32,
     charisma: i32,
-    pub willpower: i32,
+    willpower: i32,
     quickness: i32,
 
-    // TODO Deal with essence
+    // Magic before any lost to Essence; see `magic()`.
     magic: i32,
+    nuyen: i32,
+    karma_pool: i32,
+    karma: i32,
+    karma_earned: i32,
+
+    stun_level: i32,
+    phys_level: i32,
 
+    // Tables go last so the struct can be written out as TOML.
     skills: HashMap<Skill, SkillLevel>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    specialties: HashMap<Skill, Specialty>,
+    knowledge: HashMap<Skill, SkillLevel>,
     spells: HashMap<SpellName, ForceLevel>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    cyberware: Vec<Implant>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    sustaining: Vec<SustainedSpell>,
+    #[serde(rename = "purchase", skip_serializing_if = "Vec::is_empty")]
+    ledger: Vec<Purchase>,
+    armor: Option<Armor>,
 
-    stun_level: i32,
-    phys_level: i32,
+    // Pools come back full by the next session anyway.
+    #[serde(skip)]
+    pools: PoolState,
+    #[serde(skip, default = "stdout_sink")]
+    sink: Rc<dyn EventSink>,
+}
 
-    roller: &'a T
+fn stdout_sink() -> Rc<dyn EventSink> {
+    Rc::new(StdoutSink)
 }
 
-impl<'a, S:Roller + 'a> Character<'a, S> {
-    pub fn new(name: &'static str, race: Race, roller: &'a S)
-               -> Character<'a, S> {
+// Everyone starts with a 1 in each attribute, adjusted for their metatype but
+// never below 1.
+fn starting(race: Race, attr: Attribute) -> i32 {
+    max(1, 1 + race.modifier(attr))
+}
+
+impl Character {
+    pub fn new(name: &str, race: Race) -> Character {
         Character {
-            name: name,
-            race: race,
-            body: 0,
-            intelligence: 0,
-            strength: 0,
-            charisma: 0,
-            willpower: 0,
-            quickness: 0,
-            magic: 6, // TODO assuming awakened
+            name: name.to_owned(),
+            race,
+            body: starting(race, Attribute::Body),
+            intelligence: starting(race, Attribute::Intelligence),
+            strength: starting(race, Attribute::Strength),
+            charisma: starting(race, Attribute::Charisma),
+            willpower: starting(race, Attribute::Willpower),
+            quickness: starting(race, Attribute::Quickness),
+            // Mundane until generation or `set_magic` awakens them.
+            magic: 0,
+            nuyen: 0,
+            karma_pool: 1,
+            karma: 0,
+            karma_earned: 0,
 
             skills: HashMap::new(),
+            specialties: HashMap::new(),
+            knowledge: HashMap::new(),
             spells: HashMap::new(),
+            cyberware: Vec::new(),
+            sustaining: Vec::new(),
+            ledger: Vec::new(),
+            armor: None,
 
             phys_level: 0,
             stun_level: 0,
-            roller: roller,
+            pools: PoolState::default(),
+            sink: stdout_sink(),
+        }
+    }
+
+    /// Sends this character's events (falling unconscious, dying, drain...)
+    /// somewhere other than stdout.
+    pub fn set_sink(&mut self, sink: Rc<dyn EventSink>) {
+        self.sink = sink
+    }
+
+    fn emit(&self, event: Event) {
+        self.sink.emit(&event)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn race(&self) -> Race {
+        self.race
+    }
+
+    /// Sets an attribute, racial modifiers included, so long as it's within
+    /// the metatype's limit.
+    pub fn set_attr(&mut self, attr: Attribute, value: i32) -> Result<(), AttrError> {
+        let limit = self.race.limit(attr);
+        if value > limit {
+            return Err(AttrError::OverRacialLimit(self.race, attr, limit));
+        }
+        match attr {
+            Attribute::Body => self.body = value,
+            Attribute::Willpower => self.willpower = value,
+            Attribute::Strength => self.strength = value,
+            Attribute::Intelligence => self.intelligence = value,
+            Attribute::Quickness => self.quickness = value,
+            Attribute::Charisma => self.charisma = value,
+        }
+        Ok(())
+    }
+
+    /// An attribute without any augmentation.
+    pub fn natural_attr(&self, attr: Attribute) -> i32 {
+        match attr {
+            Attribute::Body => self.body,
+            Attribute::Willpower => self.willpower,
+            Attribute::Strength => self.strength,
+            Attribute::Intelligence => self.intelligence,
+            Attribute::Quickness => self.quickness,
+            Attribute::Charisma => self.charisma,
+        }
+    }
+
+    /// Magic as it stands after Essence loss: every point of Essence gone,
+    /// or part of one, costs a point of Magic.
+    pub fn magic(&self) -> i32 {
+        if self.magic == 0 {
+            return 0;
+        }
+        let lost = (FULL_ESSENCE - self.essence() + 99) / 100;
+        max(0, self.magic - lost)
+    }
+
+    pub fn set_magic(&mut self, magic: i32) {
+        self.magic = magic
+    }
+
+    pub fn nuyen(&self) -> i32 {
+        self.nuyen
+    }
+
+    pub fn set_nuyen(&mut self, nuyen: i32) {
+        self.nuyen = nuyen
+    }
+
+    pub fn set_karma_pool(&mut self, dice: i32) {
+        self.karma_pool = dice
+    }
+
+    /// Good Karma earned and not yet spent.
+    pub fn karma(&self) -> i32 {
+        self.karma
+    }
+
+    pub fn karma_earned(&self) -> i32 {
+        self.karma_earned
+    }
+
+    /// Awards Good Karma. Every tenth point a human earns, or twentieth for
+    /// metahumans, goes to the Karma Pool instead.
+    pub fn earn_karma(&mut self, amount: i32) {
+        let every = if self.race == Race::Human { 10 } else { 20 };
+        for _ in 0..amount {
+            self.karma_earned += 1;
+            if self.karma_earned % every == 0 {
+                self.karma_pool += 1;
+            } else {
+                self.karma += 1;
+            }
+        }
+    }
+
+    /// Spends Karma Pool to reroll every failed die in `roll`: a point the
+    /// first time, two the second, and so on.
+    pub fn reroll_failures<R: Roller>(&mut self, roller: &R, roll: &mut RollResult)
+                                      -> Result<(), PoolError> {
+        if roll.dice.iter().all(|d| d.total() > roll.target) {
+            return Err(PoolError::NothingToReroll);
+        }
+        let cost = roll.rerolls() + 1;
+        let left = self.pool_left(Pool::Karma);
+        self.pools.spend(Pool::Karma, cost, left)?;
+        let dice = roll.reroll_failures(roller);
+        roll.karma.push(KarmaSpend::Reroll { dice, cost });
+        Ok(())
+    }
+
+    /// Spends a point of Karma Pool to make a catastrophic failure a plain
+    /// one.
+    pub fn avoid_disaster(&mut self, roll: &mut RollResult) -> Result<(), PoolError> {
+        if !roll.catastrophic_fail {
+            return Err(PoolError::NotCatastrophic);
+        }
+        let left = self.pool_left(Pool::Karma);
+        self.pools.spend(Pool::Karma, 1, left)?;
+        roll.catastrophic_fail = false;
+        roll.karma.push(KarmaSpend::AvoidedDisaster);
+        Ok(())
+    }
+
+    /// Adds a success to a roll that got at least one. The point of Karma
+    /// Pool doesn't come back next turn: it's gone until bought again.
+    pub fn buy_success(&mut self, roll: &mut RollResult) -> Result<(), PoolError> {
+        if !roll.success {
+            return Err(PoolError::NoSuccesses);
+        }
+        let left = self.pool_left(Pool::Karma);
+        if left < 1 {
+            return Err(PoolError::NotEnough(Pool::Karma, left));
+        }
+        self.karma_pool -= 1;
+        roll.successes += 1;
+        roll.karma.push(KarmaSpend::BoughtSuccess);
+        Ok(())
+    }
+
+    /// How many dice `pool` holds at the start of a turn. Combat Pool is
+    /// half of Quickness, Intelligence and Willpower together; Magic Pool is
+    /// half of Sorcery and Intelligence, for the Awakened only.
+    pub fn pool_size(&self, pool: Pool) -> i32 {
+        match pool {
+            Pool::Combat => (self.attr(Attribute::Quickness) + self.attr(Attribute::Intelligence)
+                             + self.attr(Attribute::Willpower)) / 2,
+            Pool::Magic if self.magic() > 0 =>
+                (self.skill("sorcery") + self.attr(Attribute::Intelligence)) / 2,
+            Pool::Magic => 0,
+            Pool::Karma => self.karma_pool,
+        }
+    }
+
+    /// What's left in `pool` this turn.
+    pub fn pool_left(&self, pool: Pool) -> i32 {
+        max(0, self.pool_size(pool) - self.pools.spent(pool))
+    }
+
+    /// Sets `dice` from the right pool aside for the next `test`. They're
+    /// gone from the pool until the next turn whether or not the test comes.
+    pub fn commit(&mut self, test: PoolTest, dice: i32) -> Result<(), PoolError> {
+        let left = self.pool_left(test.pool());
+        self.pools.commit(test, dice, left)
+    }
+
+    pub fn committed(&self, test: PoolTest) -> i32 {
+        self.pools.committed(test)
+    }
+
+    /// Hands over the dice committed to `test`, for the test to roll.
+    pub fn take_committed(&mut self, test: PoolTest) -> i32 {
+        self.pools.take(test)
+    }
+
+    /// Fills every pool back up at the start of a combat turn.
+    pub fn refresh_pools(&mut self) {
+        self.pools.refresh()
+    }
+
+    pub fn essence(&self) -> Essence {
+        FULL_ESSENCE - self.cyberware.iter().map(|i| i.essence_cost()).sum::<Essence>()
+    }
+
+    pub fn cyberware(&self) -> &[Implant] {
+        &self.cyberware
+    }
+
+    /// Installs `implant`, so long as it isn't already there and there's
+    /// Essence left over afterwards.
+    pub fn install(&mut self, implant: Implant) -> Result<(), CyberError> {
+        if self.cyberware.iter().any(|i| i.ware == implant.ware) {
+            return Err(CyberError::AlreadyInstalled(implant.ware));
         }
+        if self.essence() - implant.essence_cost() <= 0 {
+            return Err(CyberError::NotEnoughEssence(self.essence()));
+        }
+        self.cyberware.push(implant);
+        Ok(())
+    }
+
+    /// How many d6 the character rolls for initiative.
+    pub fn initiative_dice(&self) -> i32 {
+        1 + self.cyberware.iter().map(|i| i.initiative_dice()).sum::<i32>()
+    }
+
+    /// Reaction plus a d6 for each initiative die, less wound modifiers.
+    pub fn roll_initiative<R: Roller>(&self, roller: &R) -> Initiative {
+        let dice = (0..self.initiative_dice()).map(|_| roller.roll_die(false)).collect();
+        Initiative::new(self.reaction(), dice, self.injury_to_mod())
+    }
+
+    /// Armor that's part of the character: trollish skin, dermal plating.
+    pub fn dermal_armor(&self) -> i32 {
+        self.race.dermal_armor() + self.cyberware.iter().map(|i| i.armor()).sum::<i32>()
+    }
+
+    /// Puts on `armor`, taking off whatever was worn before.
+    pub fn wear(&mut self, armor: Armor) -> Option<Armor> {
+        self.armor.replace(armor)
+    }
+
+    pub fn take_off_armor(&mut self) -> Option<Armor> {
+        self.armor.take()
     }
 
-    pub fn set_roller(&mut self, roller: &'a S) -> () {
-        self.roller = roller
+    pub fn armor(&self) -> Option<&Armor> {
+        self.armor.as_ref()
     }
 
-    pub fn learn_skill(&mut self, skill: Skill) -> () {
-        self.skills.insert(skill, 1);
+    /// Everything between the character and an attack: worn armor's
+    /// `rating` plus dermal armor.
+    pub fn armor_rating(&self, rating: ArmorRating) -> i32 {
+        self.armor.as_ref().map_or(0, |a| a.rating(rating)) + self.dermal_armor()
     }
 
-    pub fn improve_skill(&mut self, skill: Skill) -> () {
-        match self.skills.get_mut(skill) {
-            Some(old_level) => *old_level += 1,
-            None => (),
+    /// Rolls Body, plus any Combat Pool committed to damage resistance,
+    /// against the incoming power less armor, and stages the incoming level
+    /// down one for every two successes. Wounds don't make damage any harder
+    /// to resist.
+    pub fn resist_damage<R: Roller>(&mut self, roller: &R, incoming: DamageCode,
+                                    rating: ArmorRating) -> Resistance {
+        let tn = max(MIN_RESISTANCE_TN, incoming.power - self.armor_rating(rating));
+        let dice = self.attr(Attribute::Body) + self.take_committed(PoolTest::DamageResistance);
+        let roll = roller.roll(dice, tn);
+        let damage = incoming.level.stage(-(roll.successes / 2));
+        Resistance { roll, damage }
+    }
+
+    pub fn learn_skill(&mut self, skill: &str) {
+        self.skills.insert(skill.to_owned(), 1);
+    }
+
+    pub fn improve_skill(&mut self, skill: &str) {
+        if let Some(old_level) = self.skills.get_mut(skill) {
+            *old_level += 1
         }
     }
 
-    pub fn improve_skill_by(&mut self, skill: Skill, amount: SkillLevel) -> () {
+    pub fn improve_skill_by(&mut self, skill: &str, amount: SkillLevel) {
         for _ in 0..amount {
             self.improve_skill(skill)
         };
     }
 
-    pub fn skill(&self, skill: Skill) -> SkillLevel {
+    /// A skill's rating with no particular focus in play; a concentration
+    /// or specialization can be asked for by name too.
+    pub fn skill(&self, skill: &str) -> SkillLevel {
+        self.skill_rating(skill, &[])
+    }
+
+    /// The rating for a test of `skill` where `context` (a weapon, a
+    /// concentration) might bring a specialty to bear.
+    pub fn skill_rating(&self, skill: &str, context: &[&str]) -> SkillLevel {
+        let mut names = vec![skill];
+        names.extend_from_slice(context);
         // TODO case insensitive
-        match self.skills.get(skill) {
-            Some(level) => *level,
-            None => 0,
+        if let Some(&rating) = self.skills.get(skill) {
+            return match self.specialties.get(skill) {
+                Some(specialty) => specialty.rating_for(rating, &names),
+                None => rating,
+            };
+        }
+        self.specialties.iter()
+            .filter(|&(_, specialty)| specialty.covers(skill))
+            .filter_map(|(base, specialty)| self.skills.get(base)
+                        .map(|&rating| specialty.rating_for(rating, &names)))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Takes a concentration, a specialization or both in `skill`, replacing
+    /// any it had; neither gives the skill back its single rating.
+    pub fn specialize(&mut self, skill: &str, concentration: Option<&str>,
+                      specialization: Option<&str>) -> Result<(), SkillError> {
+        let rating = *self.skills.get(skill)
+            .ok_or_else(|| SkillError::NotKnown(skill.to_owned()))?;
+        if concentration.is_none() && specialization.is_none() {
+            self.specialties.remove(skill);
+            return Ok(());
+        }
+        if rating < 2 {
+            return Err(SkillError::TooLow(skill.to_owned(), rating));
+        }
+        self.specialties.insert(skill.to_owned(), Specialty {
+            concentration: concentration.map(|c| c.to_owned()),
+            specialization: specialization.map(|s| s.to_owned()),
+        });
+        Ok(())
+    }
+
+    pub fn specialty(&self, skill: &str) -> Option<&Specialty> {
+        self.specialties.get(skill)
+    }
+
+    /// The rating `skill` was bought at, before any specialty split it.
+    pub fn base_skill(&self, skill: &str) -> SkillLevel {
+        self.skills.get(skill).cloned().unwrap_or(0)
+    }
+
+    /// The dice to roll for `skill` in `context`: its own rating if the
+    /// character has it, otherwise whichever of the best related skill or
+    /// the linked attribute gives more dice, the skill winning ties.
+    pub fn skill_dice(&self, skill: &str, context: &[&str]) -> (i32, Option<Defaulting>) {
+        let rating = self.skill_rating(skill, context);
+        if rating > 0 || !skills::can_default(skill) {
+            return (rating, None);
+        }
+        let attr = skills::linked_attribute(skill);
+        let best = skills::related(skill).into_iter()
+            .map(|s| (self.skill(s), s))
+            .max_by_key(|&(rating, _)| rating);
+        match best {
+            Some((rating, s)) if rating > 0 && rating >= self.attr(attr) =>
+                (rating, Some(Defaulting::Skill(s.to_owned()))),
+            _ => (self.attr(attr), Some(Defaulting::Attribute(attr))),
         }
     }
 
-    pub fn skill_test(&self, skill: Skill, tn: TargetNumber) -> RollResult {
-        self.roll(self.skill(skill), tn)
+    /// Tests `skill`, at a specialty's rating if `context` calls for one and
+    /// defaulting at a higher target number if need be.
+    pub fn skill_test<R: Roller>(&self, roller: &R, skill: &str, context: &[&str],
+                                 tn: TargetNumber) -> RollResult {
+        let (dice, defaulted) = self.skill_dice(skill, context);
+        self.roll_defaulted(roller, dice, tn, defaulted)
     }
 
-    pub fn learn_spell(&mut self, spell_name:SpellName) -> () {
-        self.spells.insert(spell_name, 1);
+    /// Rolls `die` for a skill test, with any defaulting penalty on `tn`.
+    pub fn roll_defaulted<R: Roller>(&self, roller: &R, die: i32, tn: TargetNumber,
+                                     defaulted: Option<Defaulting>) -> RollResult {
+        let penalty = defaulted.as_ref().map_or(0, |d| d.penalty());
+        RollResult { defaulted, ..self.roll(roller, die, tn + penalty) }
     }
 
-    pub fn improve_spell(&mut self, spell_name:SpellName) -> () {
-        match self.spells.get_mut(spell_name) {
-            Some(old_force) => *old_force += 1,
-            None => (),
+    pub fn learn_spell(&mut self, spell_name: &str) {
+        self.spells.insert(spell_name.to_owned(), 1);
+    }
+
+    pub fn improve_spell(&mut self, spell_name: &str) {
+        if let Some(old_force) = self.spells.get_mut(spell_name) {
+            *old_force += 1
         }
     }
 
-    pub fn improve_spell_by(&mut self, spell_name: SpellName, amount: ForceLevel)
-                            -> ()
+    pub fn improve_spell_by(&mut self, spell_name: &str, amount: ForceLevel)
     {
         for _ in 0..amount {
             self.improve_spell(spell_name)
         };
     }
 
-    pub fn spell_force(&self, spell_name: SpellName) -> ForceLevel {
+    pub fn spell_force(&self, spell_name: &str) -> ForceLevel {
         // TODO case insensitive
         match self.spells.get(spell_name) {
             Some(f) => *f,
@@ -123,38 +608,121 @@ impl<'a, S:Roller + 'a> Character<'a, S> {
         }
     }
 
-    fn calculate_drain<T:SpellTargetNumber>
-        (&mut self, spell: &Spell<T>)
+    pub fn learn_knowledge(&mut self, skill: &str) {
+        self.knowledge.insert(skill.to_owned(), 1);
+    }
+
+    pub fn knowledge(&self, skill: &str) -> SkillLevel {
+        self.knowledge.get(skill).cloned().unwrap_or(0)
+    }
+
+    /// Raises an attribute, skill or spell a point, paid for out of Good
+    /// Karma; a skill or spell not yet known is learned at 1. The
+    /// `learn_`/`improve_` methods are for the GM, and come free.
+    pub fn advance(&mut self, kind: Advance, name: &str) -> Result<Purchase, AdvanceError> {
+        let (rating, cost) = match kind {
+            Advance::Attribute => {
+                let attr: Attribute = name.parse()
+                    .map_err(|_| AdvanceError::NoSuchAttribute(name.to_owned()))?;
+                let rating = self.natural_attr(attr) + 1;
+                let limit = self.race.limit(attr);
+                if rating > limit {
+                    let e = AttrError::OverRacialLimit(self.race, attr, limit);
+                    return Err(AdvanceError::Attribute(e));
+                }
+                (rating, attribute_cost(rating))
+            },
+            Advance::Skill => {
+                let rating = self.base_skill(name) + 1;
+                let linked = self.natural_attr(skills::linked_attribute(name));
+                (rating, active_skill_cost(rating, linked))
+            },
+            Advance::Knowledge => {
+                let rating = self.knowledge(name) + 1;
+                let intelligence = self.natural_attr(Attribute::Intelligence);
+                (rating, knowledge_skill_cost(rating, intelligence))
+            },
+            Advance::Spell => {
+                if self.magic() == 0 {
+                    return Err(AdvanceError::Unawakened);
+                }
+                let force = self.spell_force(name) + 1;
+                (force, spell_cost(force))
+            },
+        };
+        if cost > self.karma {
+            return Err(AdvanceError::NotEnoughKarma(cost, self.karma));
+        }
+
+        let bought = match kind {
+            Advance::Attribute => {
+                let attr = name.parse().expect("parsed above");
+                self.set_attr(attr, rating).expect("checked against the limit above");
+                Purchase::now(kind, &format!("{:?}", attr), rating, cost)
+            },
+            Advance::Skill => {
+                self.skills.insert(name.to_owned(), rating);
+                Purchase::now(kind, name, rating, cost)
+            },
+            Advance::Knowledge => {
+                self.knowledge.insert(name.to_owned(), rating);
+                Purchase::now(kind, name, rating, cost)
+            },
+            Advance::Spell => {
+                self.spells.insert(name.to_owned(), rating);
+                Purchase::now(kind, name, rating, cost)
+            },
+        };
+        self.karma -= cost;
+        self.ledger.push(bought.clone());
+        Ok(bought)
+    }
+
+    /// Everything bought with Karma, oldest first.
+    pub fn ledger(&self) -> &[Purchase] {
+        &self.ledger
+    }
+
+    fn calculate_drain<R:Roller, T:SpellTargetNumber>
+        (&mut self, roller: &R, spell: &Spell<T>, force: ForceLevel)
          -> Option<DamageLevel>
     {
         // doing a raw dice roll since drain doesn't take any modifiers into
         // account
-        let num_die = self.attr(Attribute::Willpower);
-        let force = self.spell_force(spell.name);
-        let drain_roll = self.roller.roll(num_die, spell.drain_modifier + (force / 2));
-        if drain_roll.success {
-            return None;
-        }
+        let num_die = self.attr(Attribute::Willpower) + self.take_committed(PoolTest::Drain);
+        let drain_roll = roller.roll(num_die, spell.drain.target_number(force));
+        // Every two successes take the drain down a level.
+        let level = spell.drain.level.resolve(force).stage(-(drain_roll.successes / 2))?;
 
-        // TODO lessen damage by a level per 2 successes
-        let damage_type = if force > self.magic {
+        let damage_type = if force > self.magic() || level == DamageLevel::Deadly {
             DamageType::Physical
         } else {
             DamageType::Stun
         };
-        self.injure(damage_type, common::dmg_to_num(spell.drain_level));
-        Some(spell.drain_level)
+        self.emit(Event::DrainTaken {
+            name: self.name.clone(),
+            level,
+            kind: damage_type,
+        });
+        self.injure(damage_type, common::dmg_to_num(level));
+        Some(level)
     }
 
-    fn sorcery_test<T:SpellTargetNumber>
-        (&self, spell: &Spell<T>, tn: TargetNumber)
+    fn sorcery_test<R:Roller, T:SpellTargetNumber>
+        (&mut self, roller: &R, spell: &Spell<T>, force: ForceLevel, tn: TargetNumber)
          -> RollResult
     {
-        if 0 == self.spell_force(spell.name) {
+        let pool = self.take_committed(PoolTest::Spellcasting);
+        // Only the Awakened cast, and only spells they know.
+        if force < 1 || self.magic() == 0 || 0 == self.spell_force(&spell.name) {
             return RollResult {
                 success: false,
                 successes: 0,
                 catastrophic_fail: false,
+                dice: Vec::new(),
+                target: tn,
+                karma: Vec::new(),
+                defaulted: None,
             }
         }
 
@@ -163,48 +731,146 @@ impl<'a, S:Roller + 'a> Character<'a, S> {
                 success: false,
                 successes: 0,
                 catastrophic_fail: false,
+                dice: Vec::new(),
+                target: tn,
+                karma: Vec::new(),
+                defaulted: None,
             }
         }
-        self.skill_test("sorcery", tn)
+        let dice = self.skill("sorcery") + pool;
+        self.roll(roller, dice, tn)
+    }
+
+    /// Casts `spell` at its learned force.
+    pub fn cast<R:Roller, T:SpellTargetNumber>(&mut self, roller: &R, spell: &Spell<T>)
+                                               -> SpellResult {
+        let force = self.spell_force(&spell.name);
+        self.cast_with(roller, spell, force, None)
     }
 
-    pub fn cast<T:SpellTargetNumber>(&mut self, spell: &Spell<T>) -> SpellResult {
-        let sorcery_test = self.sorcery_test(spell, spell.to_tn(self));
+    pub fn cast_at<R:Roller, T:SpellTargetNumber>(&mut self, roller: &R, spell: &Spell<T>,
+                                                  target: &mut Character) -> SpellResult {
+        let force = self.spell_force(&spell.name);
+        self.cast_with(roller, spell, force, Some(target))
+    }
+
+    /// Casts `spell` at `force`, on `target` or else on the caster. Casting
+    /// below the learned force is free; every point above it adds
+    /// `OVERCAST_PENALTY` to the target number. A combat spell that works
+    /// hurts its target, and a sustained one stays up until it's dropped.
+    /// Drain is resisted against the force actually cast.
+    pub fn cast_with<R:Roller, T:SpellTargetNumber>(&mut self, roller: &R, spell: &Spell<T>,
+                                                    force: ForceLevel,
+                                                    target: Option<&mut Character>)
+                                                    -> SpellResult {
+        let tn = match target {
+            Some(ref target) => spell.to_tn(&**target),
+            None => spell.to_tn(self),
+        };
+        let overcast = max(0, force - self.spell_force(&spell.name));
+        let sorcery_test = self.sorcery_test(roller, spell, force,
+                                             tn + overcast * OVERCAST_PENALTY);
         if !sorcery_test.success {
             return SpellResult::from_roll(sorcery_test, None);
         }
 
+        if spell.duration == Duration::Sustained {
+            self.sustaining.push(SustainedSpell {
+                spell: spell.name.clone(),
+                force,
+                target: target.as_ref().map(|t| t.name.clone()),
+            });
+        }
+
+        let mut resistance = None;
+        let mut damage = None;
+        if let (Some(hurts), Some(target)) = (spell.damage, target) {
+            let resisted = target.resist_spell(roller, spell, force, sorcery_test.successes);
+            if let Some(level) = resisted.damage {
+                target.injure(hurts.kind, common::dmg_to_num(level));
+                damage = Some((level, hurts.kind));
+            }
+            resistance = Some(resisted.roll);
+        }
+
         // Drain
-        let damage = self.calculate_drain(spell);
+        let drain = self.calculate_drain(roller, spell, force);
 
-        SpellResult::from_roll(sorcery_test, damage)
+        SpellResult {
+            resistance,
+            damage,
+            ..SpellResult::from_roll(sorcery_test, drain)
+        }
     }
 
-    pub fn cast_at<T,K>(&mut self, spell: &Spell<T>, target: &K) -> SpellResult
-        where T: SpellTargetNumber, K: HasAttrs
-    {
-        let sorcery_test = self.sorcery_test(spell, spell.to_tn(target));
-        if !sorcery_test.success {
-            return SpellResult::from_roll(sorcery_test, None);
-        }
+    /// Resists a combat spell cast at `force` with the attribute its type
+    /// calls for, plus any Magic Pool committed to spell defense, against a
+    /// target number of the force. The caster's `successes` have to come out
+    /// ahead of these for the spell to do anything, and every two net
+    /// successes stage its damage up a level.
+    pub fn resist_spell<R:Roller, T:SpellTargetNumber>(&mut self, roller: &R, spell: &Spell<T>,
+                                                       force: ForceLevel, successes: i32)
+                                                       -> Resistance {
+        let dice = self.attr(spell.kind.resisted_with())
+            + self.take_committed(PoolTest::SpellDefense);
+        let roll = roller.roll(dice, force);
+        let net = successes - roll.successes;
+        let damage = match spell.damage {
+            Some(hurts) if net > 0 => hurts.level.resolve(force).stage(net / 2),
+            _ => None,
+        };
+        Resistance { roll, damage }
+    }
 
-        // Drain
-        let damage = self.calculate_drain(spell);
+    /// The spells this character is keeping up, oldest first.
+    pub fn sustaining(&self) -> &[SustainedSpell] {
+        &self.sustaining
+    }
+
+    /// Stops sustaining the oldest spell called `spell_name`, handing it
+    /// back if there was one.
+    pub fn drop_spell(&mut self, spell_name: &str) -> Option<SustainedSpell> {
+        let i = self.sustaining.iter()
+            .position(|s| s.spell.eq_ignore_ascii_case(spell_name))?;
+        Some(self.sustaining.remove(i))
+    }
 
-        SpellResult::from_roll(sorcery_test, damage)
+    /// Looks `spell_name` up in `grimoire` and casts it, or `None` if it
+    /// isn't in there.
+    pub fn cast_named<R:Roller>(&mut self, roller: &R, grimoire: &Grimoire, spell_name: &str)
+                                -> Option<SpellResult> {
+        grimoire.find(spell_name).map(|spell| match *spell {
+            KnownSpell::Fixed(ref s) => self.cast(roller, s),
+            KnownSpell::Resisted(ref s) => self.cast(roller, s),
+        })
+    }
+
+    pub fn cast_named_at<R:Roller>(&mut self, roller: &R, grimoire: &Grimoire, spell_name: &str,
+                                   target: &mut Character) -> Option<SpellResult> {
+        grimoire.find(spell_name).map(|spell| match *spell {
+            KnownSpell::Fixed(ref s) => self.cast_at(roller, s, target),
+            KnownSpell::Resisted(ref s) => self.cast_at(roller, s, target),
+        })
     }
 
     pub fn reaction(&self) -> i32 {
-        (self.intelligence + self.quickness) / 2
+        let natural = (self.attr(Attribute::Intelligence) + self.attr(Attribute::Quickness)) / 2;
+        natural + self.cyberware.iter().map(|i| i.reaction_bonus()).sum::<i32>()
     }
 
+    /// Marks off `amount` boxes. Stun beyond the end of the stun track
+    /// spills over into physical damage. Anyone knocked out drops every spell
+    /// they were sustaining.
     pub fn injure(&mut self, kind: DamageType, amount: i32) -> &Self {
         match kind {
             DamageType::Stun => {
-                if self.stun_level + amount >= 10 {
-                    self.phys_level += amount - (10 - self.stun_level);
+                let room = 10 - self.stun_level;
+                if amount >= room {
                     self.stun_level = 10;
-                    println!("WARNING: {} has fallen unconscious.", self.name);
+                    self.emit(Event::KnockedOut { name: self.name.clone() });
+                    if amount > room {
+                        self.injure(DamageType::Physical, amount - room);
+                    }
                 } else {
                     self.stun_level += amount;
                 }
@@ -212,41 +878,70 @@ impl<'a, S:Roller + 'a> Character<'a, S> {
             DamageType::Physical => {
                 self.phys_level += amount;
                 if self.phys_level > 10 {
-                    println!("WARNING: {} has died.", self.name);
+                    self.emit(Event::Died { name: self.name.clone() });
                 }
             }
         };
+        if self.stun_level >= 10 || self.phys_level >= 10 {
+            for dropped in self.sustaining.split_off(0) {
+                self.emit(Event::SpellDropped { name: self.name.clone(), spell: dropped.spell });
+            }
+        }
         self
     }
 
     fn injury_to_mod(&self) -> i32 {
         match max(self.stun_level, self.phys_level) {
             0 => 0,
-            1...2 => 1,
-            3...5 => 2,
+            1..=2 => 1,
+            3..=5 => 2,
             _ => 3,
         }
     }
 
-    pub fn roll(&self, die: i32, tn: TargetNumber) -> RollResult {
+    pub fn roll<R: Roller>(&self, roller: &R, die: i32, tn: TargetNumber) -> RollResult {
         if self.phys_level > 10 || self.stun_level > 10 {
-            println!("WARNING rolling for dead or unconscious character");
+            self.emit(Event::RolledWhileIncapacitated { name: self.name.clone() });
         }
-        let tn = self.injury_to_mod() + tn;
-        return self.roller.roll(die, tn);
+        let sustaining = SUSTAINING_PENALTY * self.sustaining.len() as i32;
+        let tn = self.injury_to_mod() + sustaining + tn;
+        roller.roll(die, tn)
     }
 }
 
-impl<'a, T:Roller + 'a> HasAttrs for Character<'a, T> {
+impl Default for Character {
+    fn default() -> Character {
+        Character::new("", Race::Human)
+    }
+}
+
+impl HasAttrs for Character {
+    /// The attribute with cyberware included, up to the racial maximum.
     fn attr(&self, attr:Attribute) -> i32 {
-        match attr {
-            Attribute::Body => self.body,
-            Attribute::Willpower => self.willpower,
-            Attribute::Strength => self.strength,
-            Attribute::Intelligence => self.intelligence,
-            Attribute::Quickness => self.quickness,
-            Attribute::Charisma => self.charisma,
+        let natural = self.natural_attr(attr);
+        let bonus: i32 = self.cyberware.iter().map(|i| i.attr_bonus(attr)).sum();
+        if bonus == 0 {
+            natural
+        } else {
+            min(natural + bonus, self.race.maximum(attr))
+        }
+    }
+}
+
+impl fmt::Display for Character {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({:?}) B{} Q{} S{} C{} I{} W{} M{} E{} | stun {}/10 phys {}/10",
+               self.name, self.race,
+               self.attr(Attribute::Body), self.attr(Attribute::Quickness),
+               self.attr(Attribute::Strength), self.attr(Attribute::Charisma),
+               self.attr(Attribute::Intelligence), self.attr(Attribute::Willpower),
+               self.magic(), format_essence(self.essence()),
+               self.stun_level, self.phys_level)?;
+        if !self.sustaining.is_empty() {
+            let spells: Vec<String> = self.sustaining.iter().map(|s| s.to_string()).collect();
+            write!(f, " | sustaining {}", spells.join(", "))?;
         }
+        Ok(())
     }
 }
 
@@ -254,10 +949,34 @@ impl<'a, T:Roller + 'a> HasAttrs for Character<'a, T> {
 
 #[cfg(test)]
 mod tests {
-    use character::{Race, Character};
-    use dice::{Roller};
+    use advancement::{Advance, AdvanceError};
+    use character::{AttrError, Race, Character, Vision};
+    use cyberware::{CyberError, Cyberware, Grade, Implant};
+    use armor::{Armor, ArmorRating};
+    use weapons::DamageCode;
+    use dice::{Die, KarmaSpend, Roller, RollResult, SeededRoller};
     use common::{HasAttrs, Attribute, DamageType, DamageLevel};
-    use magic::Spell;
+    use magic::{Category, Drain, Duration, Spell, SpellDamage, SpellRange, SpellTargetNumber,
+                SpellType, SustainedSpell};
+    use grimoire::Grimoire;
+    use pools::{Pool, PoolError, PoolTest};
+    use skills::{Defaulting, SkillError};
+    use events::{Event, EventLog};
+    use std::cmp::{max, min};
+    use std::rc::Rc;
+
+    fn spell<T: SpellTargetNumber>(name: &str, drain: Drain, target: T) -> Spell<T> {
+        Spell {
+            name: name.to_owned(),
+            category: Category::Manipulation,
+            kind: SpellType::Mana,
+            duration: Duration::Instant,
+            range: SpellRange::LineOfSight,
+            drain,
+            target,
+            damage: None,
+        }
+    }
 
     struct DummyRoller {
         verbose: bool,
@@ -268,7 +987,7 @@ mod tests {
         fn verbose(&self) -> bool { self.verbose }
         fn new(verbose:bool) -> Self {
             DummyRoller {
-                verbose: verbose,
+                verbose,
                 value: 5,
             }
         }
@@ -279,16 +998,15 @@ mod tests {
     impl DummyRoller {
         fn newv(verbose:bool, value:i32) -> Self {
             DummyRoller {
-                verbose: verbose,
-                value: value,
+                verbose,
+                value,
             }
         }
     }
 
     #[test]
     fn test_spell_learning() {
-        let roller = DummyRoller::new(false);
-        let mut c = Character::new("jak", Race::Elf, &roller);
+        let mut c = Character::new("jak", Race::Elf);
         assert_eq!(c.spell_force("manabolt"), 0);
         c.learn_spell("manabolt");
         assert_eq!(c.spell_force("manabolt"), 1);
@@ -300,8 +1018,7 @@ mod tests {
 
     #[test]
     fn test_attrs() {
-        let roller = DummyRoller::new(false);
-        let mut c = Character::new("flarf", Race::Dwarf, &roller);
+        let mut c = Character::new("flarf", Race::Dwarf);
         c.body = 1;
         c.willpower = 2;
         c.strength = 3;
@@ -317,10 +1034,323 @@ mod tests {
         assert_eq!(c.attr(Attribute::Charisma), 6);
     }
 
+    #[test]
+    fn test_metatypes() {
+        let troll = Character::new("acid", Race::Troll);
+        let elf = Character::new("jak", Race::Elf);
+        assert_eq!(troll.attr(Attribute::Body), 6);
+        assert_eq!(troll.attr(Attribute::Strength), 5);
+        assert_eq!(troll.attr(Attribute::Intelligence), 1);
+        assert_eq!(elf.attr(Attribute::Body), 1);
+        assert_eq!(elf.attr(Attribute::Charisma), 3);
+
+        assert_eq!(Race::Troll.limit(Attribute::Body), 11);
+        assert_eq!(Race::Troll.limit(Attribute::Intelligence), 4);
+        assert_eq!(Race::Troll.maximum(Attribute::Body), 17);
+        assert_eq!(Race::Human.maximum(Attribute::Charisma), 9);
+        assert_eq!(Race::Troll.vision(), Vision::Thermographic);
+        assert_eq!(Race::Ork.vision(), Vision::LowLight);
+        assert_eq!(Race::Human.vision(), Vision::Normal);
+        assert_eq!(Race::Troll.dermal_armor(), 1);
+        assert_eq!(Race::Dwarf.dermal_armor(), 0);
+    }
+
+    #[test]
+    fn test_racial_limits() {
+        let mut troll = Character::new("acid", Race::Troll);
+        assert_eq!(troll.set_attr(Attribute::Body, 11), Ok(()));
+        assert_eq!(troll.attr(Attribute::Body), 11);
+        assert_eq!(troll.set_attr(Attribute::Intelligence, 5),
+                   Err(AttrError::OverRacialLimit(Race::Troll, Attribute::Intelligence, 4)));
+        assert_eq!(troll.attr(Attribute::Intelligence), 1);
+
+        let mut human = Character::new("juli", Race::Human);
+        assert!(human.set_attr(Attribute::Body, 7).is_err());
+        assert!(human.set_attr(Attribute::Body, 6).is_ok());
+    }
+
+    #[test]
+    fn test_cyberware() {
+        let mut c = Character::new("sam", Race::Ork);
+        c.set_attr(Attribute::Quickness, 4).unwrap();
+        c.set_attr(Attribute::Intelligence, 4).unwrap();
+        c.set_attr(Attribute::Strength, 8).unwrap();
+        assert_eq!(c.essence(), 600);
+        assert_eq!(c.magic(), 0);
+        c.set_magic(6);
+        assert_eq!(c.magic(), 6);
+        assert_eq!(c.initiative_dice(), 1);
+
+        c.install(Implant::new(Cyberware::WiredReflexes, 2, Grade::Standard).unwrap())
+            .unwrap();
+        assert_eq!(c.essence(), 300);
+        assert_eq!(c.magic(), 3);
+        assert_eq!(c.reaction(), 8);
+        assert_eq!(c.initiative_dice(), 3);
+
+        c.install(Implant::new(Cyberware::Smartlink, 1, Grade::Alpha).unwrap()).unwrap();
+        // Even a fraction of a point of Essence costs a point of Magic.
+        assert_eq!(c.essence(), 260);
+        assert_eq!(c.magic(), 2);
+
+        c.install(Implant::new(Cyberware::Cyberarm, 1, Grade::Standard).unwrap()).unwrap();
+        assert_eq!(c.attr(Attribute::Strength), 9);
+        c.install(Implant::new(Cyberware::DermalPlating, 1, Grade::Standard).unwrap())
+            .unwrap();
+        assert_eq!(c.dermal_armor(), 1);
+        assert_eq!(c.essence(), 110);
+
+        assert_eq!(c.install(Implant::new(Cyberware::Cyberarm, 1, Grade::Delta).unwrap()),
+                   Err(CyberError::AlreadyInstalled(Cyberware::Cyberarm)));
+        let mut d = Character::new("dim", Race::Human);
+        d.set_magic(6);
+        d.install(Implant::new(Cyberware::WiredReflexes, 3, Grade::Standard).unwrap()).unwrap();
+        assert_eq!(d.install(Implant::new(Cyberware::DermalPlating, 2, Grade::Standard).unwrap()),
+                   Err(CyberError::NotEnoughEssence(100)));
+        assert_eq!(d.magic(), 1);
+        // A mundane has no Magic to lose, however much Essence goes.
+        let mut m = Character::new("sam", Race::Human);
+        m.install(Implant::new(Cyberware::WiredReflexes, 3, Grade::Standard).unwrap()).unwrap();
+        assert_eq!(m.essence(), 100);
+        assert_eq!(m.magic(), 0);
+        assert_eq!(Character::new("troll", Race::Troll).dermal_armor(), 1);
+    }
+
+    #[test]
+    fn test_initiative() {
+        let roller = DummyRoller::newv(false, 6);
+        let mut c = Character::new("sam", Race::Human);
+        c.set_attr(Attribute::Quickness, 5).unwrap();
+        c.set_attr(Attribute::Intelligence, 4).unwrap();
+        // Initiative dice don't explode.
+        assert_eq!(c.roll_initiative(&roller).score, 10);
+        c.install(Implant::new(Cyberware::WiredReflexes, 1, Grade::Standard).unwrap()).unwrap();
+        let init = c.roll_initiative(&roller);
+        assert_eq!(init.dice.len(), 2);
+        assert_eq!(init.score, 18);
+        c.injure(DamageType::Physical, 3);
+        assert_eq!(c.roll_initiative(&roller).score, 16);
+    }
+
+    #[test]
+    fn test_pools() {
+        let roller = DummyRoller::newv(false, 5);
+        let mut c = Character::new("rose", Race::Human);
+        c.set_magic(6);
+        c.set_attr(Attribute::Quickness, 4).unwrap();
+        c.set_attr(Attribute::Intelligence, 5).unwrap();
+        c.set_attr(Attribute::Willpower, 3).unwrap();
+        c.set_attr(Attribute::Body, 2).unwrap();
+        c.learn_skill("sorcery");
+        c.improve_skill_by("sorcery", 2);
+        assert_eq!(c.pool_size(Pool::Combat), 6);
+        assert_eq!(c.pool_size(Pool::Magic), 4);
+        assert_eq!(c.pool_size(Pool::Karma), 1);
+        c.set_karma_pool(3);
+        assert_eq!(c.pool_size(Pool::Karma), 3);
+
+        // Committed dice leave the pool at once and join the next test.
+        c.commit(PoolTest::DamageResistance, 2).unwrap();
+        assert_eq!(c.pool_left(Pool::Combat), 4);
+        assert_eq!(c.commit(PoolTest::Dodge, 5), Err(PoolError::NotEnough(Pool::Combat, 4)));
+        let r = c.resist_damage(&roller, "4M".parse().unwrap(), ArmorRating::Ballistic);
+        assert_eq!(r.roll.successes, 4);
+        assert_eq!(r.damage, None);
+        assert_eq!(c.committed(PoolTest::DamageResistance), 0);
+        assert_eq!(c.pool_left(Pool::Combat), 4);
+        c.refresh_pools();
+        assert_eq!(c.pool_left(Pool::Combat), 6);
+
+        c.learn_spell("oxygenate");
+        c.commit(PoolTest::Spellcasting, 2).unwrap();
+        let sr = c.cast_named(&roller, &Grimoire::core(), "oxygenate").unwrap();
+        assert_eq!(sr.successes, 5);
+        assert_eq!(c.pool_left(Pool::Magic), 2);
+
+        c.set_magic(0);
+        assert_eq!(c.pool_size(Pool::Magic), 0);
+    }
+
+    #[test]
+    fn test_karma() {
+        let mut c = Character::new("rose", Race::Human);
+        c.earn_karma(25);
+        assert_eq!((c.karma(), c.karma_earned(), c.pool_size(Pool::Karma)), (23, 25, 3));
+        let mut elf = Character::new("frank", Race::Elf);
+        elf.earn_karma(25);
+        assert_eq!((elf.karma(), elf.pool_size(Pool::Karma)), (24, 2));
+
+        let die = |face| Die { faces: vec![face] };
+        let mut roll = RollResult::from_dice(vec![die(1), die(5), die(2)], 4);
+        c.reroll_failures(&DummyRoller::newv(false, 5), &mut roll).unwrap();
+        assert_eq!(roll.successes, 3);
+        assert_eq!(roll.karma, vec![KarmaSpend::Reroll { dice: 2, cost: 1 }]);
+        assert_eq!(c.reroll_failures(&DummyRoller::newv(false, 5), &mut roll),
+                   Err(PoolError::NothingToReroll));
+
+        // Each reroll of the same roll costs a point more than the last.
+        let mut miss = RollResult::from_dice(vec![die(2), die(3)], 4);
+        c.reroll_failures(&DummyRoller::newv(false, 3), &mut miss).unwrap();
+        assert_eq!(c.reroll_failures(&DummyRoller::newv(false, 3), &mut miss),
+                   Err(PoolError::NotEnough(Pool::Karma, 1)));
+        assert_eq!(c.buy_success(&mut miss), Err(PoolError::NoSuccesses));
+
+        // A bought success takes the point out of the pool for good.
+        c.buy_success(&mut roll).unwrap();
+        assert_eq!(roll.successes, 4);
+        assert_eq!(roll.to_string(), "[5, 5, 5] vs TN 4 \u{2192} 4 successes; \
+                                      rerolled 2 for 1 karma, bought a success");
+        assert_eq!(c.pool_left(Pool::Karma), 0);
+        c.refresh_pools();
+        assert_eq!(c.pool_left(Pool::Karma), 2);
+
+        let mut disaster = RollResult::from_dice(vec![die(1), die(1)], 4);
+        assert_eq!(c.avoid_disaster(&mut roll), Err(PoolError::NotCatastrophic));
+        c.avoid_disaster(&mut disaster).unwrap();
+        assert!(!disaster.catastrophic_fail);
+        assert!(!disaster.success);
+        assert_eq!(c.pool_left(Pool::Karma), 1);
+
+        // What karma already bought survives a reroll.
+        c.reroll_failures(&DummyRoller::newv(false, 1), &mut disaster).unwrap();
+        assert!(!disaster.catastrophic_fail);
+        c.refresh_pools();
+        let mut hit = RollResult::from_dice(vec![die(5), die(2)], 4);
+        c.buy_success(&mut hit).unwrap();
+        c.reroll_failures(&DummyRoller::newv(false, 5), &mut hit).unwrap();
+        assert_eq!(hit.successes, 3);
+        assert_eq!(hit.karma, vec![KarmaSpend::BoughtSuccess,
+                                   KarmaSpend::Reroll { dice: 1, cost: 1 }]);
+    }
+
+    #[test]
+    fn test_advancement() {
+        let mut c = Character::new("rose", Race::Human);
+        c.set_magic(6);
+        c.set_attr(Attribute::Quickness, 4).unwrap();
+        c.set_attr(Attribute::Intelligence, 3).unwrap();
+        c.earn_karma(40);
+        assert_eq!(c.karma(), 36);
+
+        assert_eq!(c.advance(Advance::Attribute, "willpower").unwrap().cost, 4);
+        assert_eq!(c.attr(Attribute::Willpower), 2);
+        // Pistols are linked to Quickness 4, and cost more past it.
+        let costs: Vec<i32> = (0..5)
+            .map(|_| c.advance(Advance::Skill, "pistols").unwrap().cost)
+            .collect();
+        assert_eq!(costs, vec![2, 3, 5, 6, 10]);
+        assert_eq!(c.skill("pistols"), 5);
+        assert_eq!(c.advance(Advance::Skill, "pistols"),
+                   Err(AdvanceError::NotEnoughKarma(12, 6)));
+        assert_eq!(c.skill("pistols"), 5);
+
+        c.advance(Advance::Knowledge, "seattle gangs").unwrap();
+        assert_eq!(c.knowledge("seattle gangs"), 1);
+        c.advance(Advance::Spell, "manabolt").unwrap();
+        assert_eq!(c.spell_force("manabolt"), 1);
+        assert_eq!(c.karma(), 4);
+
+        assert_eq!(c.advance(Advance::Attribute, "luck"),
+                   Err(AdvanceError::NoSuchAttribute("luck".to_owned())));
+        c.set_attr(Attribute::Body, 6).unwrap();
+        assert_eq!(c.advance(Advance::Attribute, "body"),
+                   Err(AdvanceError::Attribute(
+                       AttrError::OverRacialLimit(Race::Human, Attribute::Body, 6))));
+        c.set_magic(0);
+        assert_eq!(c.advance(Advance::Spell, "manabolt"), Err(AdvanceError::Unawakened));
+
+        assert_eq!(c.ledger().len(), 8);
+        assert_eq!(c.ledger()[0].to_string(), "Willpower to 2 for 4 karma");
+        assert_eq!(c.ledger()[7].to_string(), "manabolt spell to 1 for 1 karma");
+    }
+
+    #[test]
+    fn test_defaulting() {
+        let roller = DummyRoller::newv(false, 5);
+        let mut c = Character::new("rose", Race::Human);
+        c.set_attr(Attribute::Quickness, 5).unwrap();
+        assert_eq!(c.skill_dice("pistols", &[]),
+                   (5, Some(Defaulting::Attribute(Attribute::Quickness))));
+        let r = c.skill_test(&roller, "pistols", &[], 2);
+        assert_eq!(r.target, 6);
+        assert_eq!(r.successes, 0);
+
+        // A related skill as good as the attribute wins, at only +2.
+        c.learn_skill("rifles");
+        c.improve_skill_by("rifles", 4);
+        let r = c.skill_test(&roller, "pistols", &[], 2);
+        assert_eq!(r.target, 4);
+        assert_eq!(r.successes, 5);
+        assert!(r.to_string().ends_with("5 successes (defaulted to rifles)"));
+
+        assert_eq!(c.skill_test(&roller, "rifles", &[], 2).defaulted, None);
+        assert_eq!(c.skill_dice("sorcery", &[]), (0, None));
+    }
+
+    #[test]
+    fn test_specialties() {
+        let roller = DummyRoller::newv(false, 5);
+        let mut c = Character::new("rose", Race::Human);
+        c.learn_skill("firearms");
+        c.improve_skill_by("firearms", 4);
+        c.specialize("firearms", Some("pistols"), Some("ares predator")).unwrap();
+        assert_eq!(c.skill("firearms"), 4);
+        assert_eq!(c.skill("Pistols"), 6);
+        assert_eq!(c.skill_rating("firearms", &["ares predator"]), 7);
+        assert_eq!(c.skill_rating("pistols", &["ares predator"]), 7);
+        assert_eq!(c.skill_rating("firearms", &["rifles"]), 4);
+        assert_eq!(c.base_skill("firearms"), 5);
+        assert_eq!(c.skill_test(&roller, "firearms", &["ares predator"], 4).dice.len(), 7);
+        assert_eq!(c.skill_test(&roller, "pistols", &[], 4).defaulted, None);
+
+        c.specialize("firearms", None, None).unwrap();
+        assert_eq!(c.skill("firearms"), 5);
+        assert_eq!(c.specialty("firearms"), None);
+        assert_eq!(c.specialize("knitting", None, Some("socks")),
+                   Err(SkillError::NotKnown("knitting".to_owned())));
+        c.learn_skill("clubs");
+        assert_eq!(c.specialize("clubs", None, Some("batons")),
+                   Err(SkillError::TooLow("clubs".to_owned(), 1)));
+    }
+
+    #[test]
+    fn test_resist_damage() {
+        let roller = DummyRoller::newv(false, 5);
+        let heavy_pistol: DamageCode = "9M".parse().unwrap();
+        let mut c = Character::new("frank", Race::Human);
+        c.set_attr(Attribute::Body, 4).unwrap();
+
+        // Power 9 is out of reach of fives.
+        let r = c.resist_damage(&roller, heavy_pistol, ArmorRating::Ballistic);
+        assert_eq!(r.roll.successes, 0);
+        assert_eq!(r.damage, Some(DamageLevel::Moderate));
+
+        assert_eq!(c.wear(Armor::new("armor jacket", 5, 3)), None);
+        assert_eq!(c.armor_rating(ArmorRating::Ballistic), 5);
+        let r = c.resist_damage(&roller, heavy_pistol, ArmorRating::Ballistic);
+        assert_eq!(r.roll.target, 4);
+        assert_eq!(r.damage, None);
+        let r = c.resist_damage(&roller, heavy_pistol, ArmorRating::Impact);
+        assert_eq!(r.roll.target, 6);
+        assert_eq!(r.damage, Some(DamageLevel::Moderate));
+
+        // Armor never takes the target number below 2, and wounds don't
+        // raise it.
+        c.injure(DamageType::Physical, 6);
+        let r = c.resist_damage(&roller, "3L".parse().unwrap(), ArmorRating::Ballistic);
+        assert_eq!(r.roll.target, 2);
+
+        let mut troll = Character::new("acid", Race::Troll);
+        troll.wear(Armor::new("armor vest", 2, 1));
+        assert_eq!(troll.armor_rating(ArmorRating::Ballistic), 3);
+        assert_eq!(troll.take_off_armor().map(|a| a.name), Some("armor vest".to_owned()));
+        assert_eq!(troll.armor_rating(ArmorRating::Impact), 1);
+    }
+
     #[test]
     fn test_skills() {
         let roller = DummyRoller::new(false);
-        let mut c = Character::new("acid", Race::Troll, &roller);
+        let mut c = Character::new("acid", Race::Troll);
         assert_eq!(c.skill("knitting"), 0);
         c.learn_skill("knitting");
         assert_eq!(c.skill("knitting"), 1);
@@ -328,14 +1358,13 @@ mod tests {
         assert_eq!(c.skill("knitting"), 2);
         c.improve_skill_by("knitting", 5);
         assert_eq!(c.skill("knitting"), 7);
-        let result = c.skill_test("knitting", 0);
+        let result = c.skill_test(&roller, "knitting", &[], 0);
         assert!(result.success);
     }
 
     #[test]
     fn test_reaction() {
-        let roller = DummyRoller::new(false);
-        let mut c = Character::new("juli", Race::Human, &roller);
+        let mut c = Character::new("juli", Race::Human);
         c.quickness = 3;
         c.intelligence = 1;
         assert_eq!(c.reaction(), 2);
@@ -345,8 +1374,7 @@ mod tests {
 
     #[test]
     fn test_condition() {
-        let roller = DummyRoller::new(false);
-        let mut c = Character::new("hernando", Race::Elf, &roller);
+        let mut c = Character::new("hernando", Race::Elf);
         assert_eq!(c.phys_level, 0);
         assert_eq!(c.stun_level, 0);
         c.injure(DamageType::Stun, 1);
@@ -361,9 +1389,59 @@ mod tests {
     }
 
     #[test]
-    fn test_injury_mod() {
+    fn test_injury_by_level() {
+        use common::dmg_to_num;
+        use common::DamageLevel::*;
+        let levels = [(Light, 1), (Moderate, 3), (Serious, 6), (Deadly, 10)];
+        for &(level, boxes) in &levels {
+            let mut c = Character::new("hernando", Race::Elf);
+            c.injure(DamageType::Physical, dmg_to_num(level));
+            assert_eq!((c.stun_level, c.phys_level), (0, boxes), "{:?} physical", level);
+
+            let mut c = Character::new("hernando", Race::Elf);
+            c.injure(DamageType::Stun, dmg_to_num(level));
+            assert_eq!((c.stun_level, c.phys_level), (boxes, 0), "{:?} stun", level);
+
+            // On top of a Serious stun wound, the rest spills into physical.
+            let mut c = Character::new("hernando", Race::Elf);
+            c.injure(DamageType::Stun, 6);
+            c.injure(DamageType::Stun, dmg_to_num(level));
+            assert_eq!((c.stun_level, c.phys_level), (min(10, 6 + boxes), max(0, boxes - 4)),
+                       "{:?} stun on Serious stun", level);
+        }
+        let mut c = Character::new("hernando", Race::Elf);
+        c.injure(DamageType::Stun, dmg_to_num(Variable.resolve(5)));
+        assert_eq!(c.stun_level, 3);
+    }
+
+    #[test]
+    fn test_condition_events() {
         let roller = DummyRoller::new(false);
-        let mut c = Character::new("francine", Race::Dwarf, &roller);
+        let log = Rc::new(EventLog::new());
+        let mut c = Character::new("dodger", Race::Human);
+        c.set_sink(log.clone());
+        // Stun that spills over far enough is deadly too.
+        c.injure(DamageType::Stun, 22);
+        assert_eq!(log.take(), vec![Event::KnockedOut { name: "dodger".to_owned() },
+                                    Event::Died { name: "dodger".to_owned() }]);
+
+        let log = Rc::new(EventLog::new());
+        let mut c = Character::new("hernando", Race::Elf);
+        c.set_sink(log.clone());
+        c.injure(DamageType::Stun, 4);
+        assert!(log.take().is_empty());
+        c.injure(DamageType::Stun, 6);
+        assert_eq!(log.take(), vec![Event::KnockedOut { name: "hernando".to_owned() }]);
+        c.injure(DamageType::Physical, 11);
+        assert_eq!(log.take(), vec![Event::Died { name: "hernando".to_owned() }]);
+        c.roll(&roller, 3, 4);
+        assert_eq!(log.take(),
+                   vec![Event::RolledWhileIncapacitated { name: "hernando".to_owned() }]);
+    }
+
+    #[test]
+    fn test_injury_mod() {
+        let mut c = Character::new("francine", Race::Dwarf);
         c.injure(DamageType::Stun, 1);
         assert_eq!(c.injury_to_mod(), 1);
         c.injure(DamageType::Physical, 3);
@@ -376,34 +1454,28 @@ mod tests {
     fn test_spell_casting() {
         let roller = DummyRoller::new(false);
         // Every d6 is going to come up 5
-        let mut c = Character::new("rose", Race::Human, &roller);
+        let mut c = Character::new("rose", Race::Human);
+        c.set_magic(6);
+        let log = Rc::new(EventLog::new());
+        c.set_sink(log.clone());
 
-        let oxygenate = Spell {
-            name: "oxygenate",
-            drain_level: DamageLevel::Light,
-            drain_modifier: 2,
-            target: 4,
-        };
+        let grimoire = Grimoire::core();
+        let oxygenate = |c: &mut Character| c.cast_named(&roller, &grimoire, "oxygenate")
+            .unwrap();
 
         // A character with no sorcery can't cast spells.
-        let sr = c.cast(&oxygenate);
-        assert_eq!(sr.success, false);
+        let sr = oxygenate(&mut c);
+        assert!(!sr.success);
         assert_eq!(sr.successes, 0);
-        assert!(match sr.drain_result {
-            None => true,
-            _ => false
-        });
+        assert!(sr.drain_result.is_none());
 
         c.learn_skill("sorcery");
         c.improve_skill_by("sorcery", 2);
         // A character who doesn't know a spell can't cast it.
-        let sr = c.cast(&oxygenate);
-        assert_eq!(sr.success, false);
+        let sr = oxygenate(&mut c);
+        assert!(!sr.success);
         assert_eq!(sr.successes, 0);
-        assert!(match sr.drain_result {
-            None => true,
-            _ => false
-        });
+        assert!(sr.drain_result.is_none());
 
         c.learn_spell("oxygenate");
         c.improve_spell_by("oxygenate", 3);
@@ -411,23 +1483,248 @@ mod tests {
         // A character who knows sorcery and the spell should do fine.
         // No drain since spell's force is too low.
         c.willpower = 4;
-        let sr = c.cast(&oxygenate);
-        assert_eq!(sr.success, true);
+        let sr = oxygenate(&mut c);
+        assert!(sr.success);
         assert_eq!(sr.successes, 3);
-        assert!(match sr.drain_result {
-            None => true,
-            _ => false
-        });
+        assert!(sr.drain_result.is_none());
 
-        // Make oxygenate's force much higher to trigger drain.
+        // Make oxygenate's force much higher to trigger drain, after letting
+        // go of the first one so it doesn't get in the way.
+        assert!(c.drop_spell("oxygenate").is_some());
         c.improve_spell_by("oxygenate", 10);
-        let sr = c.cast(&oxygenate);
-        assert_eq!(sr.success, true);
+        let sr = oxygenate(&mut c);
+        assert!(sr.success);
         assert_eq!(sr.successes, 3);
-        assert!(match sr.drain_result {
-            Some(DamageLevel::Light) => true,
-            _ => false
-        });
+        assert!(matches!(sr.drain_result, Some(DamageLevel::Light)));
+        assert_eq!(log.take(), vec![Event::DrainTaken {
+            name: "rose".to_owned(),
+            level: DamageLevel::Light,
+            kind: DamageType::Physical,
+        }]);
+        assert!(c.cast_named(&roller, &grimoire, "fireball").is_none());
+    }
+
+    #[test]
+    fn test_drain_staging() {
+        let roller = DummyRoller::new(false);
+        let log = Rc::new(EventLog::new());
+        let spell = |level| spell("manabolt", Drain::new(0, level), 4);
+        let caster = |willpower| {
+            let mut c = Character::new("rose", Race::Human);
+            c.set_magic(6);
+            c.set_sink(log.clone());
+            c.set_attr(Attribute::Willpower, willpower).unwrap();
+            c.learn_skill("sorcery");
+            c.improve_skill_by("sorcery", 3);
+            c.learn_spell("manabolt");
+            c.improve_spell_by("manabolt", 5);
+            c
+        };
+
+        // Force 6 drains at TN 3, so every die succeeds.
+        let mut c = caster(1);
+        assert_eq!(c.cast(&roller, &spell(DamageLevel::Serious)).drain_result,
+                   Some(DamageLevel::Serious));
+        let mut c = caster(3);
+        assert_eq!(c.cast(&roller, &spell(DamageLevel::Serious)).drain_result,
+                   Some(DamageLevel::Moderate));
+        assert_eq!(c.stun_level, 3);
+        let mut c = caster(4);
+        assert_eq!(c.cast(&roller, &spell(DamageLevel::Serious)).drain_result,
+                   Some(DamageLevel::Light));
+        let mut c = caster(6);
+        assert_eq!(c.cast(&roller, &spell(DamageLevel::Serious)).drain_result, None);
+        log.take();
+
+        // Deadly drain is always physical, even within the caster's Magic.
+        let mut c = caster(1);
+        assert_eq!(c.cast(&roller, &spell(DamageLevel::Deadly)).drain_result,
+                   Some(DamageLevel::Deadly));
+        assert_eq!((c.stun_level, c.phys_level), (0, 10));
+        assert_eq!(log.take(), vec![Event::DrainTaken {
+            name: "rose".to_owned(),
+            level: DamageLevel::Deadly,
+            kind: DamageType::Physical,
+        }]);
+        let mut c = caster(2);
+        assert_eq!(c.cast(&roller, &spell(DamageLevel::Deadly)).drain_result,
+                   Some(DamageLevel::Serious));
+        assert_eq!((c.stun_level, c.phys_level), (6, 0));
+
+        // Variable drain settles on the force first: force 6 is Moderate.
+        let mut c = caster(2);
+        assert_eq!(c.cast(&roller, &spell(DamageLevel::Variable)).drain_result,
+                   Some(DamageLevel::Light));
+    }
+
+    #[test]
+    fn test_casting_force() {
+        let rose = || {
+            let mut c = Character::new("rose", Race::Human);
+            c.set_magic(6);
+            c.willpower = 4;
+            c.learn_skill("sorcery");
+            c.improve_skill_by("sorcery", 3);
+            c.learn_spell("manabolt");
+            c.improve_spell_by("manabolt", 3);
+            c
+        };
+        let manabolt = spell("manabolt", Drain::new(2, DamageLevel::Serious), 2);
+        let fives = DummyRoller::new(false);
+        let fours = DummyRoller::newv(false, 4);
+
+        // At force 4 fours can't resist drain at TN 4; lowered to force 1
+        // it's resisted at TN 2 and stages down to Light.
+        let mut c = rose();
+        assert_eq!(c.cast(&fours, &manabolt).drain_result, Some(DamageLevel::Serious));
+        let mut c = rose();
+        assert_eq!(c.cast_with(&fours, &manabolt, 1, None).drain_result,
+                   Some(DamageLevel::Light));
+
+        // Overcasting by one makes the sorcery test TN 4, too much for fours.
+        let mut c = rose();
+        let sr = c.cast_with(&fours, &manabolt, 5, None);
+        assert!(!sr.success);
+        assert_eq!(sr.drain_result, None);
+        let sr = c.cast_with(&fives, &manabolt, 5, None);
+        assert!(sr.success);
+        assert_eq!(sr.successes, 4);
+        assert_eq!((c.stun_level, c.phys_level), (1, 0));
+
+        // More force than Magic makes the drain physical.
+        let mut c = rose();
+        c.set_magic(4);
+        let sr = c.cast_with(&fives, &manabolt, 5, None);
+        assert_eq!(sr.drain_result, Some(DamageLevel::Light));
+        assert_eq!((c.stun_level, c.phys_level), (0, 1));
+
+        // Force has to be at least 1.
+        let sr = c.cast_with(&fives, &manabolt, 0, Some(&mut Character::new("drek", Race::Elf)));
+        assert!(!sr.success);
+        assert_eq!(sr.successes, 0);
+    }
+
+    #[test]
+    fn test_combat_spells() {
+        let roller = DummyRoller::new(false);
+        let mut rose = Character::new("rose", Race::Human);
+        rose.set_magic(6);
+        rose.willpower = 6;
+        rose.learn_skill("sorcery");
+        rose.improve_skill_by("sorcery", 3);
+        rose.learn_spell("manabolt");
+        rose.improve_spell_by("manabolt", 3);
+        rose.learn_spell("powerbolt");
+        rose.improve_spell_by("powerbolt", 3);
+        let manabolt = Spell {
+            damage: Some(SpellDamage::new(DamageLevel::Moderate, DamageType::Physical)),
+            ..spell("manabolt", Drain::new(0, DamageLevel::Moderate), Attribute::Willpower)
+        };
+        let powerbolt = Spell {
+            kind: SpellType::Physical,
+            damage: Some(SpellDamage::new(DamageLevel::Moderate, DamageType::Stun)),
+            ..spell("powerbolt", Drain::new(0, DamageLevel::Light), Attribute::Body)
+        };
+
+        // Four successes against drek's one at TN 4 stage Moderate up to
+        // Serious.
+        let mut drek = Character::new("drek", Race::Elf);
+        let sr = rose.cast_at(&roller, &manabolt, &mut drek);
+        assert_eq!(sr.successes, 4);
+        assert_eq!(sr.resistance.map(|r| r.successes), Some(1));
+        assert_eq!(sr.damage, Some((DamageLevel::Serious, DamageType::Physical)));
+        assert_eq!(drek.phys_level, 6);
+        assert_eq!(sr.drain_result, None);
+
+        // Physical spells are resisted with Body. A single net success does
+        // the spell's own damage, not its drain's...
+        let mut drek = Character::new("drek", Race::Elf);
+        drek.set_attr(Attribute::Body, 3).unwrap();
+        let sr = rose.cast_at(&roller, &powerbolt, &mut drek);
+        assert_eq!(sr.damage, Some((DamageLevel::Moderate, DamageType::Stun)));
+        assert_eq!((drek.stun_level, drek.phys_level), (3, 0));
+
+        // ...but a tie does nothing...
+        let mut drek = Character::new("drek", Race::Elf);
+        drek.set_attr(Attribute::Body, 4).unwrap();
+        let sr = rose.cast_at(&roller, &powerbolt, &mut drek);
+        assert_eq!(sr.resistance.map(|r| r.successes), Some(4));
+        assert_eq!(sr.damage, None);
+        assert_eq!(drek.stun_level, 0);
+
+        // Resisting well enough stages the damage away: six successes
+        // against four take Light down to nothing.
+        let stunbolt = Spell {
+            damage: Some(SpellDamage::new(DamageLevel::Light, DamageType::Stun)),
+            ..spell("stunbolt", Drain::new(0, DamageLevel::Light), 2)
+        };
+        rose.learn_spell("stunbolt");
+        let mut drek = Character::new("drek", Race::Elf);
+        drek.set_attr(Attribute::Willpower, 6).unwrap();
+        let sr = rose.cast_with(&roller, &stunbolt, 2, Some(&mut drek));
+        assert_eq!(sr.successes, 4);
+        assert_eq!(sr.damage, None);
+        assert_eq!(sr.resistance.map(|r| r.successes), Some(6));
+        assert_eq!(drek.stun_level, 0);
+
+        // ...and nor does the target winning by one, whatever the level.
+        let stunbolt = Spell {
+            damage: Some(SpellDamage::new(DamageLevel::Deadly, DamageType::Stun)),
+            ..stunbolt
+        };
+        let mut drek = Character::new("drek", Race::Elf);
+        drek.set_attr(Attribute::Willpower, 5).unwrap();
+        let sr = rose.cast_with(&roller, &stunbolt, 2, Some(&mut drek));
+        assert_eq!(sr.resistance.map(|r| r.successes), Some(5));
+        assert_eq!(sr.damage, None);
+        assert_eq!(drek.stun_level, 0);
+
+        // Without a target a combat spell hurts nobody.
+        let sr = rose.cast(&roller, &stunbolt);
+        assert!(sr.success);
+        assert_eq!((sr.resistance, sr.damage), (None, None));
+        assert_eq!(rose.stun_level, 0);
+    }
+
+    #[test]
+    fn test_sustained_spells() {
+        let roller = DummyRoller::new(false);
+        let log = Rc::new(EventLog::new());
+        let mut rose = Character::new("rose", Race::Human);
+        rose.set_magic(6);
+        rose.set_sink(log.clone());
+        rose.willpower = 6;
+        rose.learn_skill("sorcery");
+        rose.improve_skill_by("sorcery", 3);
+        rose.learn_spell("armor");
+        let armor = Spell {
+            duration: Duration::Sustained,
+            ..spell("armor", Drain::new(0, DamageLevel::Light), 1)
+        };
+
+        let mut drek = Character::new("drek", Race::Elf);
+        assert!(rose.cast(&roller, &armor).success);
+        assert!(rose.cast_at(&roller, &armor, &mut drek).success);
+        assert_eq!(rose.sustaining(), &[
+            SustainedSpell { spell: "armor".to_owned(), force: 1, target: None },
+            SustainedSpell { spell: "armor".to_owned(), force: 1, target: Some("drek".to_owned()) },
+        ]);
+        assert!(rose.to_string().ends_with(" | sustaining armor 1, armor 1 on drek"));
+
+        // Two spells make TN 1 into TN 5, and fives don't beat that.
+        assert_eq!(rose.roll(&roller, 4, 1).successes, 0);
+        assert_eq!(rose.drop_spell("ARMOR").map(|s| s.target), Some(None));
+        assert_eq!(rose.roll(&roller, 4, 1).successes, 4);
+        assert_eq!(rose.drop_spell("manabolt"), None);
+
+        // Getting knocked out drops everything.
+        log.take();
+        rose.injure(DamageType::Stun, 10);
+        assert!(rose.sustaining().is_empty());
+        assert_eq!(log.take(), vec![
+            Event::KnockedOut { name: "rose".to_owned() },
+            Event::SpellDropped { name: "rose".to_owned(), spell: "armor".to_owned() },
+        ]);
     }
 
     #[test]
@@ -435,29 +1732,55 @@ mod tests {
         let high_roller = DummyRoller::newv(false, 5);
         let low_roller = DummyRoller::newv(false, 2);
 
-        let mut c = Character::new("rose", Race::Human, &high_roller);
+        let mut c = Character::new("rose", Race::Human);
+        c.set_magic(6);
         c.willpower = 5;
         c.learn_spell("confuse");
         c.improve_spell_by("confuse", 4);
         c.learn_skill("sorcery");
         c.improve_spell_by("sorcery", 4);
 
-        let mut d = Character::new("drek", Race::Elf, &high_roller);
+        let mut d = Character::new("drek", Race::Elf);
         d.willpower = 4;
 
 
-        let confuse = Spell {
-            name: "confuse",
-            drain_level: DamageLevel::Serious,
-            drain_modifier: 0,
-            target: Attribute::Willpower,
-        };
+        let confuse = spell("confuse", Drain::new(0, DamageLevel::Serious),
+                            Attribute::Willpower);
 
-        let sr = c.cast_at(&confuse, &d);
+        let sr = c.cast_at(&high_roller, &confuse, &mut d);
         assert!(sr.success);
 
-        c.set_roller(&low_roller);
-        let sr = c.cast_at(&confuse, &d);
+        let sr = c.cast_at(&low_roller, &confuse, &mut d);
         assert!(!sr.success);
     }
+
+    #[test]
+    fn test_seeded_tests_replay() {
+        let first = SeededRoller::with_seed(false, 42);
+        let second = SeededRoller::with_seed(false, 42);
+
+        let mut a = Character::new("rose", Race::Human);
+        let mut b = Character::new("rose", Race::Human);
+        for c in [&mut a, &mut b].iter_mut() {
+            c.set_magic(6);
+            c.willpower = 4;
+            c.learn_skill("sorcery");
+            c.improve_skill_by("sorcery", 5);
+            c.learn_spell("oxygenate");
+            c.improve_spell_by("oxygenate", 5);
+        }
+
+        let oxygenate = spell("oxygenate", Drain::new(2, DamageLevel::Moderate), 4);
+
+        // The casts have to actually roll for the replay to mean anything.
+        let mut cast_successes = 0;
+        for _ in 0..10 {
+            assert_eq!(a.skill_test(&first, "sorcery", &[], 4),
+                       b.skill_test(&second, "sorcery", &[], 4));
+            let cast = a.cast(&first, &oxygenate);
+            cast_successes += cast.successes;
+            assert_eq!(cast, b.cast(&second, &oxygenate));
+        }
+        assert!(cast_successes > 0);
+    }
 }
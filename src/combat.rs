@@ -0,0 +1,280 @@
+//! Initiative and the order of play in a fight: who goes when, how many
+//! passes they get, and who is holding their action.
+use std::fmt;
+
+use dice::{format_dice, Die};
+
+/// How far initiative drops between one pass and the next.
+pub const PASS_LENGTH: i32 = 10;
+
+/// A combatant's initiative for one combat turn.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Initiative {
+    pub reaction: i32,
+    pub dice: Vec<Die>,
+    pub wound_modifier: i32,
+    pub score: i32,
+}
+
+impl Initiative {
+    /// Reaction plus the dice, less the wound modifier.
+    pub fn new(reaction: i32, dice: Vec<Die>, wound_modifier: i32) -> Initiative {
+        let rolled: i32 = dice.iter().map(|d| d.total()).sum();
+        Initiative {
+            reaction,
+            dice,
+            wound_modifier,
+            score: reaction + rolled - wound_modifier,
+        }
+    }
+
+    /// The score to act at in `pass` (counting from 1), if there is one.
+    pub fn score_in_pass(&self, pass: i32) -> Option<i32> {
+        let score = self.score - PASS_LENGTH * (pass - 1);
+        if score > 0 { Some(score) } else { None }
+    }
+
+    /// How many passes this initiative acts in.
+    pub fn passes(&self) -> i32 {
+        if self.score > 0 { (self.score + PASS_LENGTH - 1) / PASS_LENGTH } else { 0 }
+    }
+}
+
+impl fmt::Display for Initiative {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (reaction {} + {}", self.score, self.reaction, format_dice(&self.dice))?;
+        if self.wound_modifier != 0 {
+            write!(f, " - {} for wounds", self.wound_modifier)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// Someone's go.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Action {
+    pub name: String,
+    pub turn: i32,
+    pub pass: i32,
+    pub score: i32,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} acts at {} (turn {}, pass {})", self.name, self.score, self.turn, self.pass)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CombatError {
+    NotInCombat(String),
+    NobodyActing,
+    NotDelaying(String),
+}
+
+impl fmt::Display for CombatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CombatError::NotInCombat(ref n) => write!(f, "{} isn't in the fight", n),
+            CombatError::NobodyActing => write!(f, "it's nobody's action yet"),
+            CombatError::NotDelaying(ref n) =>
+                write!(f, "{} isn't holding an action", n),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Combatant {
+    name: String,
+    initiative: Initiative,
+    acted: bool,
+    delaying: bool,
+}
+
+/// Keeps order in a fight. Each turn everyone's initiative is handed in with
+/// `begin_turn`, then `next_action` says whose action it is until the turn is over.
+/// Ties go to the higher Reaction, and then to whoever joined first.
+#[derive(Debug, Default)]
+pub struct CombatTracker {
+    turn: i32,
+    pass: i32,
+    score: i32,
+    combatants: Vec<Combatant>,
+    current: Option<usize>,
+}
+
+impl CombatTracker {
+    pub fn new() -> CombatTracker {
+        CombatTracker::default()
+    }
+
+    pub fn turn(&self) -> i32 {
+        self.turn
+    }
+
+    pub fn pass(&self) -> i32 {
+        self.pass
+    }
+
+    /// Everyone in the fight, in initiative order.
+    pub fn names(&self) -> Vec<&str> {
+        self.combatants.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    pub fn initiative(&self, name: &str) -> Option<&Initiative> {
+        self.combatants.iter().find(|c| c.name == name).map(|c| &c.initiative)
+    }
+
+    /// Starts the next combat turn with freshly rolled initiative.
+    pub fn begin_turn(&mut self, initiatives: Vec<(String, Initiative)>) {
+        self.combatants = initiatives.into_iter().map(|(name, initiative)| Combatant {
+            name,
+            initiative,
+            acted: false,
+            delaying: false,
+        }).collect();
+        // sort_by is stable, so join order settles anything left over.
+        self.combatants.sort_by(|a, b| {
+            (b.initiative.score, b.initiative.reaction)
+                .cmp(&(a.initiative.score, a.initiative.reaction))
+        });
+        self.turn += 1;
+        self.pass = 1;
+        self.score = 0;
+        self.current = None;
+    }
+
+    /// Whose action it is next, or `None` once the turn is over. Anyone still
+    /// holding an action when the pass ends loses it.
+    pub fn next_action(&mut self) -> Option<Action> {
+        if self.turn == 0 {
+            return None;
+        }
+        loop {
+            let pass = self.pass;
+            let up = self.combatants.iter()
+                .position(|c| !c.acted && !c.delaying
+                          && c.initiative.score_in_pass(pass).is_some());
+            if let Some(i) = up {
+                let score = self.combatants[i].initiative.score_in_pass(pass)
+                    .expect("only combatants acting this pass are picked");
+                self.combatants[i].acted = true;
+                self.current = Some(i);
+                self.score = score;
+                return Some(self.action(i));
+            }
+
+            self.current = None;
+            let more = self.combatants.iter()
+                .any(|c| c.initiative.score_in_pass(pass + 1).is_some());
+            if !more {
+                return None;
+            }
+            self.pass += 1;
+            for c in &mut self.combatants {
+                c.acted = false;
+                c.delaying = false;
+            }
+        }
+    }
+
+    /// Holds the current combatant's action for later in the pass.
+    pub fn delay(&mut self) -> Result<(), CombatError> {
+        let i = self.current.take().ok_or(CombatError::NobodyActing)?;
+        self.combatants[i].acted = false;
+        self.combatants[i].delaying = true;
+        Ok(())
+    }
+
+    /// Takes a held action now, at whatever score the pass has reached.
+    pub fn act_now(&mut self, name: &str) -> Result<Action, CombatError> {
+        let i = self.combatants.iter().position(|c| c.name == name)
+            .ok_or_else(|| CombatError::NotInCombat(name.to_owned()))?;
+        if !self.combatants[i].delaying {
+            return Err(CombatError::NotDelaying(name.to_owned()));
+        }
+        self.combatants[i].delaying = false;
+        self.combatants[i].acted = true;
+        self.current = Some(i);
+        Ok(self.action(i))
+    }
+
+    fn action(&self, i: usize) -> Action {
+        Action {
+            name: self.combatants[i].name.clone(),
+            turn: self.turn,
+            pass: self.pass,
+            score: self.score,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use combat::{Action, CombatError, CombatTracker, Initiative};
+    use dice::Die;
+
+    fn init(reaction: i32, faces: &[i32]) -> Initiative {
+        let dice = faces.iter().map(|&f| Die { faces: vec![f] }).collect();
+        Initiative::new(reaction, dice, 0)
+    }
+
+    fn act(name: &str, turn: i32, pass: i32, score: i32) -> Option<Action> {
+        Some(Action { name: name.to_owned(), turn, pass, score })
+    }
+
+    #[test]
+    fn test_initiative() {
+        let i = init(6, &[4, 5]);
+        assert_eq!(i.score, 15);
+        assert_eq!(i.passes(), 2);
+        assert_eq!(i.score_in_pass(2), Some(5));
+        assert_eq!(i.score_in_pass(3), None);
+        assert_eq!(init(4, &[6]).passes(), 1);
+        assert_eq!(init(4, &[6, 1]).passes(), 2);
+
+        let hurt = Initiative::new(3, vec![Die { faces: vec![2] }], 2);
+        assert_eq!(hurt.score, 3);
+        assert_eq!(hurt.to_string(), "3 (reaction 3 + [2] - 2 for wounds)");
+    }
+
+    #[test]
+    fn test_passes_and_ties() {
+        let mut tracker = CombatTracker::new();
+        assert_eq!(tracker.next_action(), None);
+        tracker.begin_turn(vec![("jill".to_owned(), init(4, &[3])),
+                                ("sam".to_owned(), init(8, &[2, 4])),
+                                ("frank".to_owned(), init(5, &[2])),
+                                ("acid".to_owned(), init(3, &[4]))]);
+        assert_eq!(tracker.names(), vec!["sam", "frank", "jill", "acid"]);
+        assert_eq!(tracker.next_action(), act("sam", 1, 1, 14));
+        // Frank and Jill tie on 7; Frank's Reaction is higher.
+        assert_eq!(tracker.next_action(), act("frank", 1, 1, 7));
+        assert_eq!(tracker.next_action(), act("jill", 1, 1, 7));
+        assert_eq!(tracker.next_action(), act("acid", 1, 1, 7));
+        assert_eq!(tracker.next_action(), act("sam", 1, 2, 4));
+        assert_eq!(tracker.next_action(), None);
+
+        tracker.begin_turn(vec![("jill".to_owned(), init(4, &[3]))]);
+        assert_eq!(tracker.next_action(), act("jill", 2, 1, 7));
+    }
+
+    #[test]
+    fn test_delaying() {
+        let mut tracker = CombatTracker::new();
+        assert_eq!(tracker.delay(), Err(CombatError::NobodyActing));
+        tracker.begin_turn(vec![("jill".to_owned(), init(4, &[6, 6])),
+                                ("frank".to_owned(), init(5, &[2]))]);
+        assert_eq!(tracker.next_action(), act("jill", 1, 1, 16));
+        tracker.delay().unwrap();
+        assert_eq!(tracker.act_now("frank"),
+                   Err(CombatError::NotDelaying("frank".to_owned())));
+        assert_eq!(tracker.next_action(), act("frank", 1, 1, 7));
+        assert_eq!(tracker.act_now("jill").ok(), act("jill", 1, 1, 7));
+        assert_eq!(tracker.next_action(), act("jill", 1, 2, 6));
+        // Held actions are lost at the end of the pass.
+        tracker.delay().unwrap();
+        assert_eq!(tracker.next_action(), None);
+        assert_eq!(tracker.act_now("bob"), Err(CombatError::NotInCombat("bob".to_owned())));
+    }
+}
@@ -1,6 +1,9 @@
+use std::cmp::{max, min};
+use std::str::FromStr;
+
 pub type TargetNumber = i32;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Attribute {
     Willpower,
     Intelligence,
@@ -10,13 +13,41 @@ pub enum Attribute {
     Charisma,
 }
 
-#[derive(Debug)]
+impl FromStr for Attribute {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Attribute, ()> {
+        match s.to_lowercase().as_str() {
+            "willpower" => Ok(Attribute::Willpower),
+            "intelligence" => Ok(Attribute::Intelligence),
+            "body" => Ok(Attribute::Body),
+            "quickness" => Ok(Attribute::Quickness),
+            "strength" => Ok(Attribute::Strength),
+            "charisma" => Ok(Attribute::Charisma),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum DamageType {
     Stun,
     Physical,
 }
 
-#[derive(Debug, Copy, Clone)]
+impl FromStr for DamageType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<DamageType, ()> {
+        match s.to_lowercase().as_str() {
+            "stun" => Ok(DamageType::Stun),
+            "physical" | "phys" => Ok(DamageType::Physical),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DamageLevel {
     Light,
     Moderate,
@@ -25,16 +56,113 @@ pub enum DamageLevel {
     Variable,
 }
 
+impl DamageLevel {
+    const STAGES: [DamageLevel; 4] = [
+        DamageLevel::Light,
+        DamageLevel::Moderate,
+        DamageLevel::Serious,
+        DamageLevel::Deadly,
+    ];
+
+    fn rank(self) -> Option<i32> {
+        DamageLevel::STAGES.iter().position(|&l| l == self).map(|r| r as i32)
+    }
+
+    /// The level from the letter it goes by in damage and drain codes.
+    pub fn from_letter(letter: char) -> Option<DamageLevel> {
+        match letter.to_ascii_uppercase() {
+            'L' => Some(DamageLevel::Light),
+            'M' => Some(DamageLevel::Moderate),
+            'S' => Some(DamageLevel::Serious),
+            'D' => Some(DamageLevel::Deadly),
+            'V' => Some(DamageLevel::Variable),
+            _ => None,
+        }
+    }
+
+    pub fn letter(self) -> char {
+        match self {
+            DamageLevel::Light => 'L',
+            DamageLevel::Moderate => 'M',
+            DamageLevel::Serious => 'S',
+            DamageLevel::Deadly => 'D',
+            DamageLevel::Variable => 'V',
+        }
+    }
+
+    /// Moves the level up by `levels`, or down if it's negative, stopping at
+    /// Deadly. `None` means it was staged away to nothing. Variable has no
+    /// place to stage from until it's resolved, so it stays as it is.
+    pub fn stage(self, levels: i32) -> Option<DamageLevel> {
+        let rank = match self.rank() {
+            Some(rank) => rank + levels,
+            None => return Some(self),
+        };
+        if rank < 0 {
+            return None;
+        }
+        Some(DamageLevel::STAGES[min(rank, 3) as usize])
+    }
+
+    /// Settles a Variable level from the force or power behind it: Light up
+    /// to 3, Moderate up to 6, Serious up to 9 and Deadly beyond. Any other
+    /// level is already settled.
+    pub fn resolve(self, force: i32) -> DamageLevel {
+        match self {
+            DamageLevel::Variable =>
+                DamageLevel::STAGES[min(max(force - 1, 0) / 3, 3) as usize],
+            level => level,
+        }
+    }
+}
+
 pub trait HasAttrs {
     fn attr(&self, attr:Attribute) -> i32;
 }
 
+/// How many boxes of the condition monitor a level of damage fills.
+/// Variable damage has to be resolved first, and fills nothing until it is.
 pub fn dmg_to_num(dlvl: DamageLevel) -> i32 {
     match dlvl {
         DamageLevel::Light => 1,
         DamageLevel::Moderate => 3,
         DamageLevel::Serious => 6,
-        // TODO variable
-        _ => 0,
+        DamageLevel::Deadly => 10,
+        DamageLevel::Variable => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{dmg_to_num, DamageLevel};
+    use common::DamageLevel::*;
+
+    #[test]
+    fn test_staging() {
+        assert_eq!(Light.stage(0), Some(Light));
+        assert_eq!(Light.stage(1), Some(Moderate));
+        assert_eq!(Light.stage(3), Some(Deadly));
+        assert_eq!(Moderate.stage(5), Some(Deadly));
+        assert_eq!(Deadly.stage(-1), Some(Serious));
+        assert_eq!(Deadly.stage(-3), Some(Light));
+        assert_eq!(Deadly.stage(-4), None);
+        assert_eq!(Light.stage(-1), None);
+        assert_eq!(Variable.stage(2), Some(Variable));
+    }
+
+    #[test]
+    fn test_variable() {
+        let resolved: Vec<DamageLevel> = (0..12).map(|f| Variable.resolve(f)).collect();
+        assert_eq!(resolved, vec![Light, Light, Light, Light, Moderate, Moderate, Moderate,
+                                  Serious, Serious, Serious, Deadly, Deadly]);
+        assert_eq!(Serious.resolve(1), Serious);
+        assert_eq!(Variable.resolve(8).stage(1), Some(Deadly));
+    }
+
+    #[test]
+    fn test_boxes() {
+        let boxes: Vec<i32> = [Light, Moderate, Serious, Deadly, Variable].iter()
+            .map(|&l| dmg_to_num(l)).collect();
+        assert_eq!(boxes, vec![1, 3, 6, 10, 0]);
     }
 }
@@ -0,0 +1,1347 @@
+use std::fmt;
+use std::io;
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use shadowfun::advancement::{Advance, AdvanceError};
+use shadowfun::armor::wardrobe;
+use shadowfun::campaign::{Campaign, LoggedRoll};
+use shadowfun::character::{AttrError, Character, Race};
+use shadowfun::combat::{CombatError, CombatTracker};
+use shadowfun::common::{Attribute, DamageType, TargetNumber};
+use shadowfun::cyberware::{CyberError, Cyberware, Grade, Implant};
+use shadowfun::dice::Roller;
+use shadowfun::events::{Event, EventLog};
+use shadowfun::generation::{Builder, CharacterSpec, Priorities};
+use shadowfun::magic::{Category, ForceLevel, KnownSpell, SpellResult};
+use shadowfun::pools::{Pool, PoolError, PoolTest};
+use shadowfun::skills::SkillError;
+use shadowfun::notation::{Expr, ExprError};
+use shadowfun::weapons::{armory, ranged_attack, AttackError};
+
+const HELP: &str = "\
+commands:
+  new <name> <race> [npc]               create a party member or an NPC
+  set <name> <attribute|magic> <value>  set an attribute, or awaken someone
+                                        with a Magic rating
+  learn <name> skill <skill> <rating>   learn a skill at a rating
+  learn <name> spell <spell> <force>    learn a spell at a force
+  roll <dice>                           roll dice, e.g. 3d6+2, 6d6 tn5, 8d6!,
+                                        10d6 tn4 vs 6d6 tn5
+  test <name> <skill> <tn> [<context>]  make a skill test, e.g.
+                                        test sam pistols 4 \"ares predator\"
+  specialize <name> <skill> <focus> [<focus>]
+                                        take a specialization, or a
+                                        concentration and then a
+                                        specialization; 'none' drops them
+  karma <name> <amount>                 award Good Karma
+  advance <name> <attribute|skill|knowledge|spell> <what>
+                                        spend karma raising one a point
+  ledger <name>                         list what karma has bought
+  cast <name> <spell> [<target>] [force <force>]
+                                        cast a spell, optionally at someone
+                                        and at a force other than the one
+                                        learned
+  drop <name> <spell>                   stop sustaining a spell
+  pool <name> <test> <dice>             commit pool dice to the next attack,
+                                        dodge, resistance, casting, drain or
+                                        defense test this turn
+  spells [<category>]                   list the grimoire, or one category:
+                                        combat, detection, health, illusion
+                                        or manipulation
+  shoot <name> <weapon> <target> <meters>
+                                        shoot someone, e.g.
+                                        shoot sam \"heavy pistol\" frank 12
+  injure <name> <stun|physical> <boxes> mark off condition boxes
+  wear <name> <armor|nothing>           put on armor, e.g.
+                                        wear sam armor jacket
+  install <name> <cyberware> [<rating>] [<grade>]
+                                        install wired reflexes, smartlink,
+                                        cyberarm or dermal plating
+  fight <name> [<name>...]              roll initiative and start a fight
+  next                                  move on to the next action
+  delay                                 hold the current action
+  act <name>                            take a held action now
+  end                                   end the fight
+  show [<name>]                         show one or every character
+  build <name> <race> <priorities>      start generating a character, with
+                                        priorities for metatype, magic,
+                                        attributes, skills and resources, e.g.
+                                        build jill ork DABCE
+  buy <attribute|skill> <rating>        spend generation points
+  finish [npc]                          add the generated character
+  load <path> [npc]                     add a character from a TOML spec
+  save [<path>]                         save the campaign
+  help                                  show this message
+  quit                                  leave the console
+arguments containing spaces can be \"quoted\".";
+
+#[derive(Debug, PartialEq)]
+pub enum Learnable {
+    Skill,
+    Spell,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    New { name: String, race: Race, npc: bool },
+    Set { name: String, attr: Attribute, value: i32 },
+    SetMagic { name: String, value: i32 },
+    Learn { name: String, kind: Learnable, what: String, level: i32 },
+    Roll { expr: Expr },
+    Test { name: String, skill: String, tn: TargetNumber, context: Option<String> },
+    Specialize { name: String, skill: String, focus: Vec<String> },
+    Karma { name: String, amount: i32 },
+    Advance { name: String, kind: Advance, what: String },
+    Ledger { name: String },
+    Cast { name: String, spell: String, target: Option<String>, force: Option<i32> },
+    Spells { category: Option<Category> },
+    Drop { name: String, spell: String },
+    Pool { name: String, test: PoolTest, dice: i32 },
+    Shoot { name: String, weapon: String, target: String, distance: i32 },
+    Injure { name: String, kind: DamageType, amount: i32 },
+    Wear { name: String, armor: String },
+    Install { name: String, ware: Cyberware, rating: i32, grade: Grade },
+    Fight { names: Vec<String> },
+    Next,
+    Delay,
+    Act { name: String },
+    End,
+    Show { name: Option<String> },
+    Save { path: Option<String> },
+    Build { name: String, race: Race, priorities: Priorities },
+    Buy { what: String, rating: i32 },
+    Finish { npc: bool },
+    Load { path: String, npc: bool },
+    Help,
+    Quit,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseError {
+    UnknownCommand(String),
+    MissingArgument(&'static str),
+    TooManyArguments,
+    UnterminatedQuote,
+    BadNumber(String),
+    BadValue(&'static str, String),
+    BadRoll(ExprError),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::UnknownCommand(ref c) =>
+                write!(f, "unknown command '{}', try 'help'", c),
+            ParseError::MissingArgument(what) =>
+                write!(f, "missing argument: {}", what),
+            ParseError::TooManyArguments =>
+                write!(f, "too many arguments"),
+            ParseError::UnterminatedQuote =>
+                write!(f, "unterminated quote"),
+            ParseError::BadNumber(ref n) =>
+                write!(f, "'{}' is not a number", n),
+            ParseError::BadValue(what, ref v) =>
+                write!(f, "'{}' is not a valid {}", v, what),
+            ParseError::BadRoll(ref e) =>
+                write!(f, "bad roll: {}", e),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SessionError {
+    NoSuchCharacter(String),
+    DuplicateCharacter(String),
+    NoSuchSpell(String),
+    NotSustaining(String, String),
+    Pool(PoolError),
+    Advance(AdvanceError),
+    Skill(String, SkillError),
+    NoSuchWeapon(String),
+    NoSuchArmor(String),
+    ShootingSelf,
+    Attack(AttackError),
+    NoSavePath,
+    SaveFailed(String),
+    BadAttribute(AttrError),
+    BadCyberware(CyberError),
+    NotFighting,
+    Combat(CombatError),
+    NotBuilding,
+    Generation(String),
+}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SessionError::NoSuchCharacter(ref n) =>
+                write!(f, "no character named '{}'", n),
+            SessionError::DuplicateCharacter(ref n) =>
+                write!(f, "there is already a character named '{}'", n),
+            SessionError::NoSuchSpell(ref n) =>
+                write!(f, "no spell named '{}'", n),
+            SessionError::NotSustaining(ref n, ref s) =>
+                write!(f, "{} isn't sustaining {}", n, s),
+            SessionError::Pool(ref e) =>
+                write!(f, "{}", e),
+            SessionError::Advance(ref e) =>
+                write!(f, "{}", e),
+            SessionError::Skill(ref n, ref e) =>
+                write!(f, "{} {}", n, e),
+            SessionError::NoSuchWeapon(ref n) =>
+                write!(f, "no weapon named '{}'", n),
+            SessionError::NoSuchArmor(ref n) =>
+                write!(f, "no armor named '{}'", n),
+            SessionError::ShootingSelf =>
+                write!(f, "nobody shoots themselves at this table"),
+            SessionError::Attack(ref e) =>
+                write!(f, "{}", e),
+            SessionError::NoSavePath =>
+                write!(f, "no campaign file yet, try 'save <path>'"),
+            SessionError::SaveFailed(ref e) =>
+                write!(f, "could not save: {}", e),
+            SessionError::BadAttribute(ref e) =>
+                write!(f, "{}", e),
+            SessionError::BadCyberware(ref e) =>
+                write!(f, "{}", e),
+            SessionError::NotFighting =>
+                write!(f, "nobody is fighting, try 'fight'"),
+            SessionError::Combat(ref e) =>
+                write!(f, "{}", e),
+            SessionError::NotBuilding =>
+                write!(f, "nobody is being generated, try 'build'"),
+            SessionError::Generation(ref e) =>
+                write!(f, "{}", e),
+        }
+    }
+}
+
+fn tokenize(line: &str) -> Result<Vec<String>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while let Some(&c) = chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+        let mut token = String::new();
+        match chars.peek() {
+            None => break,
+            Some(&'"') => {
+                chars.next();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        closed = true;
+                        break;
+                    }
+                    token.push(c);
+                }
+                if !closed {
+                    return Err(ParseError::UnterminatedQuote);
+                }
+            },
+            Some(_) => {
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+            },
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Args {
+    tokens: ::std::vec::IntoIter<String>,
+}
+
+impl Args {
+    fn next(&mut self, what: &'static str) -> Result<String, ParseError> {
+        self.tokens.next().ok_or(ParseError::MissingArgument(what))
+    }
+
+    fn optional(&mut self) -> Option<String> {
+        self.tokens.next()
+    }
+
+    fn number(&mut self, what: &'static str) -> Result<i32, ParseError> {
+        let token = self.next(what)?;
+        token.parse().map_err(|_| ParseError::BadNumber(token))
+    }
+
+    fn value<T: ::std::str::FromStr>(&mut self, what: &'static str)
+                                     -> Result<T, ParseError> {
+        let token = self.next(what)?;
+        token.parse().map_err(|_| ParseError::BadValue(what, token))
+    }
+
+    /// An optional trailing `npc`.
+    fn npc(&mut self) -> Result<bool, ParseError> {
+        match self.optional() {
+            None => Ok(false),
+            Some(ref kind) if kind.eq_ignore_ascii_case("npc") => Ok(true),
+            Some(kind) => Err(ParseError::BadValue("npc", kind)),
+        }
+    }
+
+    fn rest(&mut self) -> Vec<String> {
+        self.tokens.by_ref().collect()
+    }
+
+    fn done(&mut self) -> Result<(), ParseError> {
+        match self.tokens.next() {
+            Some(_) => Err(ParseError::TooManyArguments),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses a single console line. Returns `Ok(None)` for a blank line.
+pub fn parse(line: &str) -> Result<Option<Command>, ParseError> {
+    let mut args = Args { tokens: tokenize(line)?.into_iter() };
+    let verb = match args.optional() {
+        Some(v) => v.to_lowercase(),
+        None => return Ok(None),
+    };
+
+    let command = match verb.as_str() {
+        "new" => Command::New {
+            name: args.next("name")?,
+            race: args.value("race")?,
+            npc: args.npc()?,
+        },
+        "set" => {
+            let name = args.next("name")?;
+            let what = args.next("attribute")?;
+            if what.eq_ignore_ascii_case("magic") {
+                Command::SetMagic { name, value: args.number("value")? }
+            } else {
+                let attr = what.parse().map_err(|_| ParseError::BadValue("attribute", what))?;
+                Command::Set { name, attr, value: args.number("value")? }
+            }
+        },
+        "learn" => Command::Learn {
+            name: args.next("name")?,
+            kind: match args.next("skill or spell")?.to_lowercase().as_str() {
+                "skill" => Learnable::Skill,
+                "spell" => Learnable::Spell,
+                other => return Err(ParseError::BadValue("skill or spell",
+                                                         other.to_owned())),
+            },
+            what: args.next("skill or spell name")?,
+            level: args.number("rating")?,
+        },
+        "roll" => Command::Roll {
+            expr: args.rest().join(" ").parse().map_err(ParseError::BadRoll)?,
+        },
+        "test" => Command::Test {
+            name: args.next("name")?,
+            skill: args.next("skill")?,
+            tn: args.number("target number")?,
+            context: args.optional(),
+        },
+        "specialize" => {
+            let name = args.next("name")?;
+            let skill = args.next("skill")?;
+            let mut focus = vec![args.next("specialization")?];
+            focus.extend(args.optional());
+            if focus[0].eq_ignore_ascii_case("none") {
+                focus.clear();
+            }
+            Command::Specialize { name, skill, focus }
+        },
+        "karma" => Command::Karma {
+            name: args.next("name")?,
+            amount: args.number("amount")?,
+        },
+        "advance" => Command::Advance {
+            name: args.next("name")?,
+            kind: args.value("attribute, skill, knowledge or spell")?,
+            what: args.next("what to advance")?,
+        },
+        "ledger" => Command::Ledger { name: args.next("name")? },
+        "cast" => {
+            let name = args.next("name")?;
+            let spell = args.next("spell")?;
+            let mut target = args.optional();
+            let mut force = None;
+            if target.as_ref().is_some_and(|t| t.eq_ignore_ascii_case("force")) {
+                target = None;
+                force = Some(args.number("force")?);
+            } else if let Some(token) = args.optional() {
+                if !token.eq_ignore_ascii_case("force") {
+                    return Err(ParseError::BadValue("force", token));
+                }
+                force = Some(args.number("force")?);
+            }
+            Command::Cast { name, spell, target, force }
+        },
+        "shoot" => Command::Shoot {
+            name: args.next("name")?,
+            weapon: args.next("weapon")?,
+            target: args.next("target")?,
+            distance: args.number("meters")?,
+        },
+        "injure" => Command::Injure {
+            name: args.next("name")?,
+            kind: args.value("damage type")?,
+            amount: args.number("boxes")?,
+        },
+        "wear" => {
+            let name = args.next("name")?;
+            // Armor names run to the end of the line, quoted or not.
+            let armor = args.rest().join(" ");
+            if armor.is_empty() {
+                return Err(ParseError::MissingArgument("armor"));
+            }
+            Command::Wear { name, armor }
+        },
+        "install" => Command::Install {
+            name: args.next("name")?,
+            ware: args.value("cyberware")?,
+            rating: match args.optional() {
+                Some(token) => token.parse().map_err(|_| ParseError::BadNumber(token))?,
+                None => 1,
+            },
+            grade: match args.optional() {
+                Some(token) => token.parse().map_err(|_| ParseError::BadValue("grade", token))?,
+                None => Grade::Standard,
+            },
+        },
+        "fight" => {
+            let names = args.rest();
+            if names.is_empty() {
+                return Err(ParseError::MissingArgument("name"));
+            }
+            Command::Fight { names }
+        },
+        "next" => Command::Next,
+        "delay" => Command::Delay,
+        "act" => Command::Act { name: args.next("name")? },
+        "end" => Command::End,
+        "drop" => Command::Drop {
+            name: args.next("name")?,
+            spell: args.next("spell")?,
+        },
+        "pool" => Command::Pool {
+            name: args.next("name")?,
+            test: args.value("pool test")?,
+            dice: args.number("dice")?,
+        },
+        "spells" => Command::Spells {
+            category: match args.optional() {
+                Some(token) => Some(token.parse()
+                    .map_err(|_| ParseError::BadValue("category", token))?),
+                None => None,
+            },
+        },
+        "show" => Command::Show { name: args.optional() },
+        "save" => Command::Save { path: args.optional() },
+        "build" => Command::Build {
+            name: args.next("name")?,
+            race: args.value("race")?,
+            priorities: args.value("priorities")?,
+        },
+        "buy" => Command::Buy {
+            what: args.next("attribute or skill")?,
+            rating: args.number("rating")?,
+        },
+        "finish" => Command::Finish { npc: args.npc()? },
+        "load" => Command::Load {
+            path: args.next("path")?,
+            npc: args.npc()?,
+        },
+        "help" => Command::Help,
+        "quit" | "exit" => Command::Quit,
+        _ => return Err(ParseError::UnknownCommand(verb)),
+    };
+    args.done()?;
+    Ok(Some(command))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Party,
+    Npc,
+}
+
+/// Where to find a character in the campaign.
+type Who = (Side, usize);
+
+/// A campaign loaded into the console, along with where to save it.
+pub struct Session<'a, R: Roller + 'a> {
+    roller: &'a R,
+    campaign: Campaign,
+    path: Option<PathBuf>,
+    events: Rc<EventLog>,
+    draft: Option<Builder>,
+    combat: Option<CombatTracker>,
+}
+
+impl<'a, R: Roller + 'a> Session<'a, R> {
+    pub fn new(roller: &'a R) -> Session<'a, R> {
+        Session::with_campaign(roller, Campaign::new(), None)
+    }
+
+    pub fn with_campaign(roller: &'a R, mut campaign: Campaign, path: Option<PathBuf>)
+                         -> Session<'a, R> {
+        let events = Rc::new(EventLog::new());
+        for c in campaign.party.iter_mut().chain(campaign.npcs.iter_mut()) {
+            c.set_sink(events.clone());
+        }
+        Session { roller, campaign, path, events, draft: None, combat: None }
+    }
+
+    fn list(&self, side: Side) -> &Vec<Character> {
+        match side {
+            Side::Party => &self.campaign.party,
+            Side::Npc => &self.campaign.npcs,
+        }
+    }
+
+    fn character(&self, who: Who) -> &Character {
+        &self.list(who.0)[who.1]
+    }
+
+    fn character_mut(&mut self, who: Who) -> &mut Character {
+        match who.0 {
+            Side::Party => &mut self.campaign.party[who.1],
+            Side::Npc => &mut self.campaign.npcs[who.1],
+        }
+    }
+
+    fn find(&self, name: &str) -> Result<Who, SessionError> {
+        for &side in &[Side::Party, Side::Npc] {
+            if let Some(i) = self.list(side).iter()
+                .position(|c| c.name().eq_ignore_ascii_case(name)) {
+                return Ok((side, i));
+            }
+        }
+        Err(SessionError::NoSuchCharacter(name.to_owned()))
+    }
+
+    /// The grimoire's own name for a spell.
+    fn find_spell(&self, name: &str) -> Result<String, SessionError> {
+        self.campaign.spells.find(name)
+            .map(|s| s.name().to_owned())
+            .ok_or_else(|| SessionError::NoSuchSpell(name.to_owned()))
+    }
+
+    fn add(&mut self, mut c: Character, npc: bool) -> Result<String, SessionError> {
+        if self.find(c.name()).is_ok() {
+            return Err(SessionError::DuplicateCharacter(c.name().to_owned()));
+        }
+        c.set_sink(self.events.clone());
+        let out = format!("{}", c);
+        if npc {
+            self.campaign.npcs.push(c);
+        } else {
+            self.campaign.party.push(c);
+        }
+        Ok(out)
+    }
+
+    /// Rolls initiative for everyone in `names` and starts a turn with it.
+    fn begin_turn(&mut self, names: &[String]) -> Result<String, SessionError> {
+        let mut initiatives = Vec::new();
+        for name in names {
+            let who = self.find(name)?;
+            self.character_mut(who).refresh_pools();
+            let c = self.character(who);
+            initiatives.push((c.name().to_owned(), c.roll_initiative(self.roller)));
+        }
+        let mut tracker = self.combat.take().unwrap_or_default();
+        tracker.begin_turn(initiatives);
+        let mut lines = vec![format!("turn {}:", tracker.turn())];
+        for name in tracker.names() {
+            let init = tracker.initiative(name).expect("names come from the tracker");
+            lines.push(format!("  {} {}", name, init));
+            self.log(Some(name), "initiative".to_owned(), &init.to_string());
+        }
+        self.combat = Some(tracker);
+        Ok(lines.join("\n"))
+    }
+
+    fn log(&mut self, who: Option<&str>, what: String, outcome: &str) {
+        self.campaign.roll_log.push(LoggedRoll {
+            who: who.map(|w| w.to_owned()),
+            what,
+            outcome: outcome.to_owned(),
+        });
+    }
+
+    /// Everything that has happened to the session's characters since the
+    /// last time anyone asked.
+    pub fn take_events(&self) -> Vec<Event> {
+        self.events.take()
+    }
+
+    /// Runs a command against the session, returning the text to show the
+    /// user. `Quit` and `Help` are left to the caller.
+    pub fn execute(&mut self, command: Command) -> Result<String, SessionError> {
+        match command {
+            Command::New { name, race, npc } => self.add(Character::new(&name, race), npc),
+            Command::Set { name, attr, value } => {
+                let who = self.find(&name)?;
+                self.character_mut(who).set_attr(attr, value)
+                    .map_err(SessionError::BadAttribute)?;
+                Ok(format!("{}", self.character(who)))
+            },
+            Command::SetMagic { name, value } => {
+                let who = self.find(&name)?;
+                self.character_mut(who).set_magic(value);
+                Ok(format!("{}", self.character(who)))
+            },
+            Command::Learn { name, kind, what, level } => {
+                let who = self.find(&name)?;
+                match kind {
+                    Learnable::Skill => {
+                        let skill = what.to_lowercase();
+                        let c = self.character_mut(who);
+                        c.learn_skill(&skill);
+                        c.improve_skill_by(&skill, level - 1);
+                        Ok(format!("{} has {} {}", c.name(), skill, c.skill(&skill)))
+                    },
+                    Learnable::Spell => {
+                        let spell = self.find_spell(&what)?;
+                        let c = self.character_mut(who);
+                        c.learn_spell(&spell);
+                        c.improve_spell_by(&spell, level - 1);
+                        Ok(format!("{} knows {} at force {}",
+                                   c.name(), spell, c.spell_force(&spell)))
+                    },
+                }
+            },
+            Command::Roll { expr } => {
+                let out = expr.roll(self.roller).to_string();
+                self.log(None, expr.to_string(), &out);
+                Ok(out)
+            },
+            Command::Test { name, skill, tn, context } => {
+                let who = self.find(&name)?;
+                let skill = skill.to_lowercase();
+                let context: Vec<String> = context.into_iter().map(|c| c.to_lowercase()).collect();
+                let context: Vec<&str> = context.iter().map(|c| c.as_str()).collect();
+                let out = self.character(who).skill_test(self.roller, &skill, &context, tn)
+                    .to_string();
+                let mut what = format!("{} test, TN {}", skill, tn);
+                if let Some(c) = context.first() {
+                    what.push_str(&format!(" ({})", c));
+                }
+                let name = self.character(who).name().to_owned();
+                self.log(Some(&name), what, &out);
+                Ok(out)
+            },
+            Command::Specialize { name, skill, focus } => {
+                let who = self.find(&name)?;
+                let skill = skill.to_lowercase();
+                let focus: Vec<String> = focus.iter().map(|f| f.to_lowercase()).collect();
+                let (concentration, specialization) = match focus.len() {
+                    2 => (Some(focus[0].as_str()), Some(focus[1].as_str())),
+                    1 => (None, Some(focus[0].as_str())),
+                    _ => (None, None),
+                };
+                let c = self.character_mut(who);
+                c.specialize(&skill, concentration, specialization)
+                    .map_err(|e| SessionError::Skill(c.name().to_owned(), e))?;
+                let mut ratings = vec![format!("{} {}", skill, c.skill(&skill))];
+                for focus in &focus {
+                    ratings.push(format!("{} {}", focus, c.skill_rating(&skill, &[focus])));
+                }
+                Ok(format!("{} has {}", c.name(), ratings.join(", ")))
+            },
+            Command::Karma { name, amount } => {
+                let who = self.find(&name)?;
+                let c = self.character_mut(who);
+                c.earn_karma(amount);
+                Ok(format!("{} has {} karma and a Karma Pool of {}",
+                           c.name(), c.karma(), c.pool_size(Pool::Karma)))
+            },
+            Command::Advance { name, kind, what } => {
+                let who = self.find(&name)?;
+                let what = match kind {
+                    Advance::Spell => self.find_spell(&what)?,
+                    Advance::Attribute => what,
+                    Advance::Skill | Advance::Knowledge => what.to_lowercase(),
+                };
+                let c = self.character_mut(who);
+                let bought = c.advance(kind, &what).map_err(SessionError::Advance)?;
+                Ok(format!("{} raises {}, {} left", c.name(), bought, c.karma()))
+            },
+            Command::Ledger { name } => {
+                let c = self.character(self.find(&name)?);
+                if c.ledger().is_empty() {
+                    return Ok(format!("{} hasn't spent any karma", c.name()));
+                }
+                let lines: Vec<String> = c.ledger().iter().map(|p| p.to_string()).collect();
+                Ok(lines.join("\n"))
+            },
+            Command::Cast { name, spell, target, force } => {
+                let caster = self.find(&name)?;
+                let target = match target {
+                    Some(ref t) => Some(self.find(t)?),
+                    None => None,
+                };
+                let spell = self.find_spell(&spell)?;
+                let result = cast(self.roller, &mut self.campaign, caster, target, &spell, force)
+                    .ok_or_else(|| SessionError::NoSuchSpell(spell.clone()))?;
+                let out = describe_spell(&result);
+                let mut what = match target {
+                    Some(t) => format!("cast {} at {}", spell, self.character(t).name()),
+                    None => format!("cast {}", spell),
+                };
+                if let Some(force) = force {
+                    what.push_str(&format!(" at force {}", force));
+                }
+                let name = self.character(caster).name().to_owned();
+                self.log(Some(&name), what, &out);
+                Ok(out)
+            },
+            Command::Shoot { name, weapon, target, distance } => {
+                let attacker = self.find(&name)?;
+                let defender = self.find(&target)?;
+                if attacker == defender {
+                    return Err(SessionError::ShootingSelf);
+                }
+                let weapon = armory().into_iter()
+                    .find(|w| w.name.eq_ignore_ascii_case(&weapon))
+                    .ok_or(SessionError::NoSuchWeapon(weapon))?;
+                let result = {
+                    let Campaign { ref mut party, ref mut npcs, .. } = self.campaign;
+                    let (attacker, defender) = pair(party, npcs, attacker, defender);
+                    ranged_attack(self.roller, attacker, &weapon, distance, defender)
+                        .map_err(SessionError::Attack)?
+                };
+                let out = result.to_string();
+                let what = format!("shoot {} with a {} at {}m",
+                                   self.character(defender).name(), weapon.name, distance);
+                let name = self.character(attacker).name().to_owned();
+                self.log(Some(&name), what, &out);
+                Ok(out)
+            },
+            Command::Injure { name, kind, amount } => {
+                let who = self.find(&name)?;
+                self.character_mut(who).injure(kind, amount);
+                Ok(format!("{}", self.character(who)))
+            },
+            Command::Wear { name, armor } => {
+                let who = self.find(&name)?;
+                if armor.eq_ignore_ascii_case("nothing") {
+                    self.character_mut(who).take_off_armor();
+                    return Ok(format!("{} wears no armor", self.character(who).name()));
+                }
+                let armor = wardrobe().into_iter()
+                    .find(|a| a.name.eq_ignore_ascii_case(&armor))
+                    .ok_or(SessionError::NoSuchArmor(armor))?;
+                let out = format!("{} wears {}", self.character(who).name(), armor);
+                self.character_mut(who).wear(armor);
+                Ok(out)
+            },
+            Command::Install { name, ware, rating, grade } => {
+                let who = self.find(&name)?;
+                let implant = Implant::new(ware, rating, grade)
+                    .map_err(SessionError::BadCyberware)?;
+                self.character_mut(who).install(implant)
+                    .map_err(SessionError::BadCyberware)?;
+                Ok(format!("{}", self.character(who)))
+            },
+            Command::Fight { names } => {
+                self.combat = None;
+                self.begin_turn(&names)
+            },
+            Command::Next => {
+                let tracker = self.combat.as_mut().ok_or(SessionError::NotFighting)?;
+                if let Some(action) = tracker.next_action() {
+                    return Ok(action.to_string());
+                }
+                // A new turn, for everyone who's still around.
+                let mut names: Vec<String> = tracker.names().iter()
+                    .map(|n| n.to_string()).collect();
+                names.retain(|n| self.find(n).is_ok());
+                let order = self.begin_turn(&names)?;
+                match self.combat.as_mut().and_then(|t| t.next_action()) {
+                    Some(action) => Ok(format!("{}\n{}", order, action)),
+                    None => Ok(order),
+                }
+            },
+            Command::Delay => {
+                let tracker = self.combat.as_mut().ok_or(SessionError::NotFighting)?;
+                tracker.delay().map_err(SessionError::Combat)?;
+                Ok("holding the action".to_owned())
+            },
+            Command::Act { name } => {
+                let name = self.character(self.find(&name)?).name().to_owned();
+                let tracker = self.combat.as_mut().ok_or(SessionError::NotFighting)?;
+                let action = tracker.act_now(&name).map_err(SessionError::Combat)?;
+                Ok(action.to_string())
+            },
+            Command::End => {
+                self.combat.take().ok_or(SessionError::NotFighting)?;
+                Ok("the fight is over".to_owned())
+            },
+            Command::Drop { name, spell } => {
+                let who = self.find(&name)?;
+                let c = self.character_mut(who);
+                let dropped = c.drop_spell(&spell)
+                    .ok_or_else(|| SessionError::NotSustaining(c.name().to_owned(), spell))?;
+                Ok(format!("{} stops sustaining {}", c.name(), dropped))
+            },
+            Command::Pool { name, test, dice } => {
+                let who = self.find(&name)?;
+                let c = self.character_mut(who);
+                c.commit(test, dice).map_err(SessionError::Pool)?;
+                Ok(format!("{} commits {} dice to {}, {} left in the {:?} Pool",
+                           c.name(), c.committed(test), test,
+                           c.pool_left(test.pool()), test.pool()))
+            },
+            Command::Spells { category } => {
+                let lines: Vec<String> = self.campaign.spells.spells.iter()
+                    .filter(|s| category.map_or(true, |c| s.category() == c))
+                    .map(|s| s.to_string())
+                    .collect();
+                Ok(lines.join("\n"))
+            },
+            Command::Show { name: Some(name) } => {
+                let who = self.find(&name)?;
+                Ok(format!("{}", self.character(who)))
+            },
+            Command::Show { name: None } => {
+                if self.campaign.party.is_empty() && self.campaign.npcs.is_empty() {
+                    return Ok("nobody here yet".to_owned());
+                }
+                let mut lines = Vec::new();
+                for &(side, title) in &[(Side::Party, "party:"), (Side::Npc, "npcs:")] {
+                    if !self.list(side).is_empty() {
+                        lines.push(title.to_owned());
+                        lines.extend(self.list(side).iter().map(|c| format!("  {}", c)));
+                    }
+                }
+                Ok(lines.join("\n"))
+            },
+            Command::Save { path } => {
+                if let Some(path) = path {
+                    self.path = Some(PathBuf::from(path));
+                }
+                let path = match self.path {
+                    Some(ref path) => path,
+                    None => return Err(SessionError::NoSavePath),
+                };
+                self.campaign.save(path)
+                    .map_err(|e| SessionError::SaveFailed(e.to_string()))?;
+                Ok(format!("saved to {}", path.display()))
+            },
+            Command::Build { name, race, priorities } => {
+                if self.find(&name).is_ok() {
+                    return Err(SessionError::DuplicateCharacter(name));
+                }
+                let builder = Builder::new(&name, race, priorities)
+                    .map_err(|e| SessionError::Generation(e.to_string()))?;
+                let out = builder.to_string();
+                self.draft = Some(builder);
+                Ok(out)
+            },
+            Command::Buy { what, rating } => {
+                let builder = self.draft.as_mut().ok_or(SessionError::NotBuilding)?;
+                match what.parse::<Attribute>() {
+                    Ok(attr) => builder.buy_attribute(attr, rating),
+                    Err(_) => builder.buy_skill(&what, rating),
+                }.map_err(|e| SessionError::Generation(e.to_string()))?;
+                Ok(builder.to_string())
+            },
+            Command::Finish { npc } => {
+                let builder = self.draft.take().ok_or(SessionError::NotBuilding)?;
+                self.add(builder.build(), npc)
+            },
+            Command::Load { path, npc } => {
+                let c = CharacterSpec::load(&path)
+                    .and_then(|spec| spec.build())
+                    .map_err(|e| SessionError::Generation(e.to_string()))?;
+                self.add(c, npc)
+            },
+            Command::Help => Ok(HELP.to_owned()),
+            Command::Quit => Ok(String::new()),
+        }
+    }
+}
+
+fn describe_spell(result: &SpellResult) -> String {
+    let mut outcome = if result.success {
+        format!("success ({} successes)", result.successes)
+    } else {
+        "failure".to_owned()
+    };
+    if let Some(ref resistance) = result.resistance {
+        outcome.push_str(&format!("; resisted: {}; ", resistance));
+        match result.damage {
+            Some((level, kind)) => outcome.push_str(&format!("{:?} {:?} damage", level, kind)),
+            None => outcome.push_str("no damage"),
+        }
+    }
+    match result.drain_result {
+        Some(level) => format!("{}, took {:?} drain", outcome, level),
+        None => outcome,
+    }
+}
+
+/// Two different characters from the campaign at once, the first mutably.
+fn pair<'a>(party: &'a mut [Character], npcs: &'a mut [Character], first: Who, second: Who)
+             -> (&'a mut Character, &'a mut Character) {
+    match (first, second) {
+        ((Side::Party, i), (Side::Npc, j)) => (&mut party[i], &mut npcs[j]),
+        ((Side::Npc, i), (Side::Party, j)) => (&mut npcs[i], &mut party[j]),
+        ((side, i), (_, j)) => {
+            let list = match side {
+                Side::Party => party,
+                Side::Npc => npcs,
+            };
+            if i < j {
+                let (left, right) = list.split_at_mut(j);
+                (&mut left[i], &mut right[0])
+            } else {
+                let (left, right) = list.split_at_mut(i);
+                (&mut right[0], &mut left[j])
+            }
+        },
+    }
+}
+
+/// Casts at `force`, or at the caster's learned force without one.
+fn cast<R: Roller>(roller: &R, campaign: &mut Campaign, caster: Who, target: Option<Who>,
+                   spell: &str, force: Option<ForceLevel>) -> Option<SpellResult> {
+    let Campaign { ref mut party, ref mut npcs, ref spells, .. } = *campaign;
+    let spell = spells.find(spell)?;
+    let (caster, target) = match target {
+        Some(t) if t != caster => {
+            let (caster, target) = pair(party, npcs, caster, t);
+            (caster, Some(target))
+        },
+        _ => {
+            let caster = match caster {
+                (Side::Party, i) => &mut party[i],
+                (Side::Npc, i) => &mut npcs[i],
+            };
+            (caster, None)
+        },
+    };
+    let force = force.unwrap_or_else(|| caster.spell_force(spell.name()));
+    Some(match *spell {
+        KnownSpell::Fixed(ref s) => caster.cast_with(roller, s, force, target),
+        KnownSpell::Resisted(ref s) => caster.cast_with(roller, s, force, target),
+    })
+}
+
+/// Runs the console, reading commands from `input` until it runs dry or the
+/// user quits.
+pub fn run<R, I, O>(session: &mut Session<R>, input: I, output: &mut O)
+                    -> io::Result<()>
+    where R: Roller, I: BufRead, O: Write
+{
+    let mut lines = input.lines();
+    loop {
+        write!(output, "> ")?;
+        output.flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        match parse(&line) {
+            Ok(None) => (),
+            Ok(Some(Command::Quit)) => break,
+            Ok(Some(command)) => {
+                let result = session.execute(command);
+                for event in session.take_events() {
+                    writeln!(output, "{}", event)?;
+                }
+                match result {
+                    Ok(out) => writeln!(output, "{}", out)?,
+                    Err(e) => writeln!(output, "error: {}", e)?,
+                }
+            },
+            Err(e) => writeln!(output, "error: {}", e)?,
+        }
+    }
+    writeln!(output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+    use console::{parse, run, tokenize, Command, Learnable, ParseError,
+                  Session, SessionError, Side};
+    use shadowfun::campaign::Campaign;
+    use shadowfun::character::{AttrError, Race};
+    use shadowfun::cyberware::{CyberError, Cyberware, Grade};
+    use shadowfun::weapons::AttackError;
+    use shadowfun::common::{HasAttrs, Attribute};
+    use shadowfun::dice::Roller;
+    use shadowfun::notation::{Expr, ExprError};
+    use shadowfun::advancement::AdvanceError;
+    use shadowfun::pools::{Pool, PoolError, PoolTest};
+    use shadowfun::skills::SkillError;
+
+    struct FixedRoller;
+
+    impl Roller for FixedRoller {
+        fn new(_: bool) -> Self { FixedRoller }
+        fn verbose(&self) -> bool { false }
+        fn d6(&self) -> i32 { 5 }
+    }
+
+    fn run_script(script: &str) -> String {
+        let roller = FixedRoller;
+        let mut session = Session::new(&roller);
+        let mut out = Vec::new();
+        run(&mut session, script.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("  test jill   sorcery 4 ").unwrap(),
+                   vec!["test", "jill", "sorcery", "4"]);
+        assert_eq!(tokenize("learn jill skill \"edged weapons\" 5").unwrap(),
+                   vec!["learn", "jill", "skill", "edged weapons", "5"]);
+        assert_eq!(tokenize("new \"froz boz"),
+                   Err(ParseError::UnterminatedQuote));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(parse("   ").unwrap().is_none());
+        assert!(matches!(parse("ROLL 10d6 tn4 vs 6d6 tn5"),
+                         Ok(Some(Command::Roll { expr: Expr::Opposed(..) }))));
+        assert!(matches!(parse("learn jill spell confuse 5"),
+                         Ok(Some(Command::Learn { kind: Learnable::Spell, level: 5, .. }))));
+        assert!(matches!(parse("cast jill confuse frank"),
+                         Ok(Some(Command::Cast { target: Some(_), force: None, .. }))));
+        assert!(matches!(parse("cast jill oxygenate force 2"),
+                         Ok(Some(Command::Cast { target: None, force: Some(2), .. }))));
+        assert!(matches!(parse("cast jill confuse frank Force 6"),
+                         Ok(Some(Command::Cast { target: Some(_), force: Some(6), .. }))));
+        assert!(matches!(parse("pool jill \"damage resistance\" 2"),
+                         Ok(Some(Command::Pool { test: PoolTest::DamageResistance, dice: 2, .. }))));
+        assert!(matches!(parse("wear sam armor jacket"),
+                         Ok(Some(Command::Wear { ref armor, .. })) if armor == "armor jacket"));
+        assert_eq!(parse("wear sam").unwrap_err(), ParseError::MissingArgument("armor"));
+        assert!(matches!(parse("new frank elf NPC"),
+                         Ok(Some(Command::New { npc: true, .. }))));
+        assert!(matches!(parse("save"),
+                         Ok(Some(Command::Save { path: None }))));
+        assert!(matches!(parse("build acid troll CEDAB"),
+                         Ok(Some(Command::Build { race: Race::Troll, .. }))));
+        assert!(matches!(parse("install sam \"wired reflexes\" 2 alpha"),
+                         Ok(Some(Command::Install { ware: Cyberware::WiredReflexes,
+                                                    rating: 2, grade: Grade::Alpha, .. }))));
+        assert!(matches!(parse("install sam smartlink"),
+                         Ok(Some(Command::Install { rating: 1, grade: Grade::Standard, .. }))));
+        assert!(matches!(parse("fight jill frank"),
+                         Ok(Some(Command::Fight { ref names })) if names.len() == 2));
+        assert_eq!(parse("fight").unwrap_err(), ParseError::MissingArgument("name"));
+        assert!(matches!(parse("finish npc"),
+                         Ok(Some(Command::Finish { npc: true }))));
+        assert!(matches!(parse("quit"),
+                         Ok(Some(Command::Quit))));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse("dance").unwrap_err(),
+                   ParseError::UnknownCommand("dance".to_owned()));
+        assert_eq!(parse("test jill sorcery").unwrap_err(),
+                   ParseError::MissingArgument("target number"));
+        assert_eq!(parse("test jill sorcery four").unwrap_err(),
+                   ParseError::BadNumber("four".to_owned()));
+        assert_eq!(parse("roll 6d20").unwrap_err(),
+                   ParseError::BadRoll(ExprError::UnsupportedDie(20)));
+        assert_eq!(parse("new froz gnome").unwrap_err(),
+                   ParseError::BadValue("race", "gnome".to_owned()));
+        assert_eq!(parse("injure froz fire 3").unwrap_err(),
+                   ParseError::BadValue("damage type", "fire".to_owned()));
+        assert_eq!(parse("new froz human boss").unwrap_err(),
+                   ParseError::BadValue("npc", "boss".to_owned()));
+        assert_eq!(parse("build acid troll ABC").unwrap_err(),
+                   ParseError::BadValue("priorities", "ABC".to_owned()));
+        assert_eq!(parse("cast jill confuse frank 6").unwrap_err(),
+                   ParseError::BadValue("force", "6".to_owned()));
+        assert_eq!(parse("cast jill confuse force").unwrap_err(),
+                   ParseError::MissingArgument("force"));
+        assert_eq!(parse("show froz boz").unwrap_err(),
+                   ParseError::TooManyArguments);
+    }
+
+    #[test]
+    fn test_session() {
+        let roller = FixedRoller;
+        let mut session = Session::new(&roller);
+        let new_jill = || parse("new jill ork").unwrap().unwrap();
+        session.execute(new_jill()).unwrap();
+        assert_eq!(session.execute(new_jill()).unwrap_err(),
+                   SessionError::DuplicateCharacter("jill".to_owned()));
+
+        session.execute(parse("set Jill willpower 3").unwrap().unwrap()).unwrap();
+        assert_eq!(session.character(session.find("jill").unwrap()).attr(Attribute::Willpower), 3);
+        let out = session.execute(parse("set jill magic 6").unwrap().unwrap()).unwrap();
+        assert!(out.contains("M6"));
+
+        session.execute(parse("learn jill skill sorcery 4").unwrap().unwrap()).unwrap();
+        assert_eq!(session.character(session.find("jill").unwrap()).skill("sorcery"), 4);
+
+        assert_eq!(session.execute(parse("learn jill spell fireball 3").unwrap().unwrap())
+                   .unwrap_err(),
+                   SessionError::NoSuchSpell("fireball".to_owned()));
+        let out = session.execute(parse("learn jill spell \"Mind Probe\" 3").unwrap().unwrap())
+            .unwrap();
+        assert_eq!(out, "jill knows mind probe at force 3");
+        let out = session.execute(parse("spells illusion").unwrap().unwrap()).unwrap();
+        assert!(out.starts_with("confuse: Illusion, Mana, Sustained, LineOfSight, \
+                                 drain (F/2) S, resisted by Willpower\n"));
+        assert!(out.lines().all(|l| l.contains("Illusion")));
+        assert_eq!(parse("spells fire").unwrap_err(),
+                   ParseError::BadValue("category", "fire".to_owned()));
+        assert_eq!(session.execute(parse("show frank").unwrap().unwrap()).unwrap_err(),
+                   SessionError::NoSuchCharacter("frank".to_owned()));
+        assert_eq!(session.execute(parse("set jill charisma 6").unwrap().unwrap()).unwrap_err(),
+                   SessionError::BadAttribute(
+                       AttrError::OverRacialLimit(Race::Ork, Attribute::Charisma, 5)));
+        let out = session.execute(parse("install jill wired 1").unwrap().unwrap()).unwrap();
+        assert!(out.contains("M4 E4.00"));
+        assert_eq!(session.execute(parse("install jill wired 2").unwrap().unwrap()).unwrap_err(),
+                   SessionError::BadCyberware(
+                       CyberError::AlreadyInstalled(Cyberware::WiredReflexes)));
+        assert_eq!(session.execute(parse("save").unwrap().unwrap()).unwrap_err(),
+                   SessionError::NoSavePath);
+    }
+
+    #[test]
+    fn test_generation() {
+        let roller = FixedRoller;
+        let mut session = Session::new(&roller);
+        assert_eq!(session.execute(parse("buy body 3").unwrap().unwrap()).unwrap_err(),
+                   SessionError::NotBuilding);
+        assert!(matches!(session.execute(parse("build acid troll DABCE").unwrap().unwrap()),
+                         Err(SessionError::Generation(_))));
+        let out = session.execute(parse("build acid troll CEDAB").unwrap().unwrap()).unwrap();
+        assert!(out.ends_with("15 attribute and 50 skill points left"));
+        session.execute(parse("buy Body 6").unwrap().unwrap()).unwrap();
+        let out = session.execute(parse("buy \"unarmed combat\" 6").unwrap().unwrap())
+            .unwrap();
+        assert!(out.contains("B11"));
+        assert!(out.ends_with("10 attribute and 44 skill points left"));
+        assert!(matches!(session.execute(parse("buy strength 7").unwrap().unwrap()),
+                         Err(SessionError::Generation(_))));
+        session.execute(parse("finish npc").unwrap().unwrap()).unwrap();
+
+        let acid = session.find("acid").unwrap();
+        assert_eq!(acid.0, Side::Npc);
+        assert_eq!(session.character(acid).attr(Attribute::Body), 11);
+        assert_eq!(session.character(acid).skill("unarmed combat"), 6);
+        assert_eq!(session.execute(parse("finish").unwrap().unwrap()).unwrap_err(),
+                   SessionError::NotBuilding);
+    }
+
+    #[test]
+    fn test_karma_and_advancement() {
+        let roller = FixedRoller;
+        let mut session = Session::new(&roller);
+        let mut run = |line: &str| session.execute(parse(line).unwrap().unwrap());
+        for line in &["new jill ork", "set jill quickness 4", "set jill magic 6", "new frank elf npc"] {
+            run(line).unwrap();
+        }
+        assert_eq!(run("karma jill 30").unwrap(), "jill has 29 karma and a Karma Pool of 2");
+        assert_eq!(run("advance jill skill Pistols").unwrap(),
+                   "jill raises pistols skill to 1 for 2 karma, 27 left");
+        assert_eq!(run("advance jill attribute willpower").unwrap(),
+                   "jill raises Willpower to 2 for 4 karma, 23 left");
+        assert_eq!(run("advance jill spell MANABOLT").unwrap(),
+                   "jill raises manabolt spell to 1 for 1 karma, 22 left");
+        assert_eq!(run("advance jill spell fireball").unwrap_err(),
+                   SessionError::NoSuchSpell("fireball".to_owned()));
+        assert_eq!(run("advance jill attribute luck").unwrap_err(),
+                   SessionError::Advance(AdvanceError::NoSuchAttribute("luck".to_owned())));
+        assert_eq!(run("ledger frank").unwrap(), "frank hasn't spent any karma");
+        assert_eq!(run("ledger jill").unwrap().lines().count(), 3);
+        assert_eq!(parse("advance jill power x").unwrap_err(),
+                   ParseError::BadValue("attribute, skill, knowledge or spell", "power".to_owned()));
+    }
+
+    #[test]
+    fn test_specializing() {
+        let roller = FixedRoller;
+        let mut session = Session::new(&roller);
+        let mut run = |line: &str| session.execute(parse(line).unwrap().unwrap());
+        run("new sam human").unwrap();
+        run("learn sam skill pistols 4").unwrap();
+        assert_eq!(run("specialize sam pistols \"Ares Predator\"").unwrap(),
+                   "sam has pistols 3, ares predator 5");
+        assert!(run("test sam pistols 4 \"ares predator\"").unwrap()
+                .starts_with("[5, 5, 5, 5, 5] vs TN 4"));
+        assert!(run("test sam pistols 4").unwrap().starts_with("[5, 5, 5] vs TN 4"));
+        assert_eq!(run("specialize sam pistols none").unwrap(), "sam has pistols 4");
+        assert_eq!(run("specialize sam rifles hunting").unwrap_err(),
+                   SessionError::Skill("sam".to_owned(),
+                                       SkillError::NotKnown("rifles".to_owned())));
+        assert!(matches!(parse("specialize sam firearms pistols \"ares predator\""),
+                         Ok(Some(Command::Specialize { ref focus, .. })) if focus.len() == 2));
+    }
+
+    #[test]
+    fn test_fighting() {
+        let roller = FixedRoller;
+        let mut session = Session::new(&roller);
+        assert_eq!(session.execute(parse("next").unwrap().unwrap()).unwrap_err(),
+                   SessionError::NotFighting);
+        for line in &["new jill ork", "set jill quickness 4", "set jill intelligence 4",
+                      "new frank elf npc", "set frank quickness 2"] {
+            session.execute(parse(line).unwrap().unwrap()).unwrap();
+        }
+        assert_eq!(session.execute(parse("fight jill bob").unwrap().unwrap()).unwrap_err(),
+                   SessionError::NoSuchCharacter("bob".to_owned()));
+        let order = session.execute(parse("fight frank jill").unwrap().unwrap()).unwrap();
+        assert_eq!(order, "turn 1:\n  jill 9 (reaction 4 + [5])\n  frank 6 (reaction 1 + [5])");
+
+        let next = |session: &mut Session<FixedRoller>|
+            session.execute(parse("next").unwrap().unwrap()).unwrap();
+        assert_eq!(session.execute(parse("pool jill dodge 2").unwrap().unwrap()).unwrap(),
+                   "jill commits 2 dice to dodge, 2 left in the Combat Pool");
+        assert_eq!(session.execute(parse("pool jill attack 3").unwrap().unwrap()).unwrap_err(),
+                   SessionError::Pool(PoolError::NotEnough(Pool::Combat, 2)));
+        assert_eq!(next(&mut session), "jill acts at 9 (turn 1, pass 1)");
+        session.execute(parse("delay").unwrap().unwrap()).unwrap();
+        assert_eq!(next(&mut session), "frank acts at 6 (turn 1, pass 1)");
+        assert_eq!(session.execute(parse("act JILL").unwrap().unwrap()).unwrap(),
+                   "jill acts at 6 (turn 1, pass 1)");
+        assert!(next(&mut session).ends_with("jill acts at 9 (turn 2, pass 1)"));
+        // A new turn fills the pools back up.
+        assert_eq!(session.execute(parse("pool jill attack 3").unwrap().unwrap()).unwrap(),
+                   "jill commits 3 dice to attack, 1 left in the Combat Pool");
+        session.execute(parse("end").unwrap().unwrap()).unwrap();
+        assert_eq!(session.execute(parse("delay").unwrap().unwrap()).unwrap_err(),
+                   SessionError::NotFighting);
+    }
+
+    #[test]
+    fn test_shooting() {
+        let roller = FixedRoller;
+        let mut session = Session::new(&roller);
+        for line in &["new sam human", "learn sam skill pistols 3",
+                      "new frank elf npc", "set frank body 2"] {
+            session.execute(parse(line).unwrap().unwrap()).unwrap();
+        }
+        let shoot = |session: &mut Session<FixedRoller>, line: &str|
+            session.execute(parse(line).unwrap().unwrap());
+        assert_eq!(shoot(&mut session, "shoot sam blaster frank 10").unwrap_err(),
+                   SessionError::NoSuchWeapon("blaster".to_owned()));
+        assert_eq!(shoot(&mut session, "shoot sam taser sam 1").unwrap_err(),
+                   SessionError::ShootingSelf);
+        assert_eq!(shoot(&mut session, "shoot sam taser frank 20").unwrap_err(),
+                   SessionError::Attack(AttackError::OutOfRange("taser".to_owned(), 20)));
+        assert_eq!(shoot(&mut session, "wear frank tinfoil").unwrap_err(),
+                   SessionError::NoSuchArmor("tinfoil".to_owned()));
+        assert_eq!(shoot(&mut session, "wear frank \"armor vest\"").unwrap(),
+                   "frank wears armor vest 2/1");
+        assert_eq!(shoot(&mut session, "wear frank nothing").unwrap(),
+                   "frank wears no armor");
+        // Fives miss at medium range; at short range three of them hit, and
+        // two fives can't resist power 9.
+        let out = shoot(&mut session, "shoot sam \"Heavy Pistol\" frank 12").unwrap();
+        assert!(out.starts_with("miss: "));
+        let out = shoot(&mut session, "shoot sam \"Heavy Pistol\" frank 3").unwrap();
+        assert!(out.ends_with("Serious Physical damage"));
+        assert!(session.character(session.find("frank").unwrap())
+                .to_string().contains("phys 6/10"));
+    }
+
+    #[test]
+    fn test_combat_spells() {
+        let roller = FixedRoller;
+        let mut session = Session::new(&roller);
+        for line in &["new rose human", "set rose willpower 6", "set rose magic 6",
+                      "learn rose skill sorcery 4",
+                      "learn rose spell manabolt 4", "new frank elf npc"] {
+            session.execute(parse(line).unwrap().unwrap()).unwrap();
+        }
+        // Frank's one success against four stages Moderate up to Serious.
+        let out = session.execute(parse("cast rose manabolt frank").unwrap().unwrap()).unwrap();
+        assert!(out.starts_with("success (4 successes); resisted: "));
+        assert!(out.ends_with("; Serious Physical damage"));
+        assert!(session.character(session.find("frank").unwrap())
+                .to_string().contains("phys 6/10"));
+
+        // Sustained spells stay up until they're dropped.
+        for line in &["learn rose spell oxygenate 2", "cast rose oxygenate"] {
+            session.execute(parse(line).unwrap().unwrap()).unwrap();
+        }
+        assert!(session.execute(parse("show rose").unwrap().unwrap()).unwrap()
+                .ends_with(" | sustaining oxygenate 2"));
+        assert_eq!(session.execute(parse("drop rose Oxygenate").unwrap().unwrap()).unwrap(),
+                   "rose stops sustaining oxygenate 2");
+        assert_eq!(session.execute(parse("drop rose oxygenate").unwrap().unwrap()).unwrap_err(),
+                   SessionError::NotSustaining("rose".to_owned(), "oxygenate".to_owned()));
+    }
+
+    #[test]
+    fn test_npcs_and_saving() {
+        let path = env::temp_dir().join(format!("shadowfun-console-{}.toml", process::id()));
+        let roller = FixedRoller;
+        let mut session = Session::new(&roller);
+        // Willpower 6 keeps jill from taking drain that would spoil her second
+        // cast.
+        for line in &["new jill ork", "set jill magic 6", "set jill willpower 6",
+                      "learn jill skill sorcery 4", "learn jill spell confuse 3",
+                      "new frank elf npc",
+                      "cast jill confuse frank", "cast jill confuse frank force 1",
+                      "test jill sorcery 4"] {
+            session.execute(parse(line).unwrap().unwrap()).unwrap();
+        }
+        assert_eq!(session.find("frank").unwrap().0, Side::Npc);
+        let shown = session.execute(parse("show").unwrap().unwrap()).unwrap();
+        assert!(shown.starts_with("party:\n  jill"));
+        assert!(shown.contains("npcs:\n  frank"));
+
+        let save = format!("save \"{}\"", path.display());
+        session.execute(parse(&save).unwrap().unwrap()).unwrap();
+        let campaign = Campaign::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(campaign.party[0].name(), "jill");
+        assert_eq!(campaign.npcs[0].name(), "frank");
+        assert_eq!(campaign.roll_log.len(), 3);
+        assert_eq!(campaign.roll_log[0].what, "cast confuse at frank");
+        assert_eq!(campaign.roll_log[1].what, "cast confuse at frank at force 1");
+        assert_eq!(campaign.roll_log[0].outcome, "success (4 successes)");
+        assert_eq!(campaign.roll_log[1].outcome, "success (4 successes)");
+        assert_eq!(campaign.roll_log[2].who, Some("jill".to_owned()));
+    }
+
+    #[test]
+    fn test_run() {
+        let out = run_script("new jill ork\n\
+                              set jill magic 6\n\
+                              learn jill skill sorcery 4\n\
+                              learn jill spell confuse 3\n\
+                              new frank elf\n\
+                              set frank willpower 4\n\
+                              cast jill confuse frank\n\
+                              roll 2d6+1\n\
+                              injure frank stun 10\n\
+                              bogus\n\
+                              quit\n\
+                              show\n");
+        assert!(out.contains("jill knows confuse at force 3"));
+        assert!(out.contains("success (4 successes)"));
+        assert!(out.contains("[5, 5] + 1 = 11"));
+        assert!(out.contains("WARNING: frank has fallen unconscious."));
+        assert!(out.contains("error: unknown command 'bogus'"));
+        // Nothing after quit is run: only new and set showed jill.
+        assert_eq!(out.matches("jill (Ork)").count(), 2);
+    }
+}
@@ -0,0 +1,218 @@
+//! Cyberware: what it costs in Essence and what it does for the runner
+//! who has it installed.
+use std::fmt;
+use std::str::FromStr;
+
+use common::Attribute;
+
+/// Essence in hundredths of a point, so costs like 0.2 add up exactly.
+pub type Essence = i32;
+
+/// Everyone starts out whole.
+pub const FULL_ESSENCE: Essence = 600;
+
+/// Writes Essence the way it's written on a character sheet: `5.80`.
+pub fn format_essence(essence: Essence) -> String {
+    format!("{}.{:02}", essence / 100, essence % 100)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Cyberware {
+    WiredReflexes,
+    Smartlink,
+    Cyberarm,
+    DermalPlating,
+}
+
+impl FromStr for Cyberware {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Cyberware, ()> {
+        match s.to_lowercase().replace(' ', "").as_str() {
+            "wiredreflexes" | "wired" => Ok(Cyberware::WiredReflexes),
+            "smartlink" => Ok(Cyberware::Smartlink),
+            "cyberarm" => Ok(Cyberware::Cyberarm),
+            "dermalplating" | "plating" => Ok(Cyberware::DermalPlating),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Cyberware {
+    /// The highest rating it comes in; 1 for anything without ratings.
+    pub fn max_rating(self) -> i32 {
+        match self {
+            Cyberware::WiredReflexes | Cyberware::DermalPlating => 3,
+            Cyberware::Smartlink | Cyberware::Cyberarm => 1,
+        }
+    }
+
+    /// What standard grade costs at `rating`.
+    pub fn essence_cost(self, rating: i32) -> Essence {
+        match (self, rating) {
+            (Cyberware::WiredReflexes, 1) => 200,
+            (Cyberware::WiredReflexes, 2) => 300,
+            (Cyberware::WiredReflexes, _) => 500,
+            (Cyberware::Smartlink, _) => 50,
+            (Cyberware::Cyberarm, _) => 100,
+            (Cyberware::DermalPlating, r) => 50 * r,
+        }
+    }
+}
+
+/// Better grades cost less Essence for the same 'ware.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Grade {
+    #[default]
+    Standard,
+    Alpha,
+    Beta,
+    Delta,
+}
+
+impl FromStr for Grade {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Grade, ()> {
+        match s.to_lowercase().as_str() {
+            "standard" => Ok(Grade::Standard),
+            "alpha" => Ok(Grade::Alpha),
+            "beta" => Ok(Grade::Beta),
+            "delta" => Ok(Grade::Delta),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Grade {
+    /// The share of the standard Essence cost this grade pays, in percent.
+    pub fn essence_percent(self) -> i32 {
+        match self {
+            Grade::Standard => 100,
+            Grade::Alpha => 80,
+            Grade::Beta => 60,
+            Grade::Delta => 50,
+        }
+    }
+}
+
+/// One piece of 'ware installed in a character.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Implant {
+    pub ware: Cyberware,
+    pub rating: i32,
+    #[serde(default)]
+    pub grade: Grade,
+}
+
+impl Implant {
+    pub fn new(ware: Cyberware, rating: i32, grade: Grade) -> Result<Implant, CyberError> {
+        if !(1..=ware.max_rating()).contains(&rating) {
+            return Err(CyberError::BadRating(ware, rating));
+        }
+        Ok(Implant { ware, rating, grade })
+    }
+
+    pub fn essence_cost(&self) -> Essence {
+        self.ware.essence_cost(self.rating) * self.grade.essence_percent() / 100
+    }
+
+    /// What it adds to `attr`.
+    pub fn attr_bonus(&self, attr: Attribute) -> i32 {
+        match (self.ware, attr) {
+            (Cyberware::Cyberarm, Attribute::Strength) => 1,
+            _ => 0,
+        }
+    }
+
+    pub fn reaction_bonus(&self) -> i32 {
+        match self.ware {
+            Cyberware::WiredReflexes => 2 * self.rating,
+            _ => 0,
+        }
+    }
+
+    /// Extra initiative dice.
+    pub fn initiative_dice(&self) -> i32 {
+        match self.ware {
+            Cyberware::WiredReflexes => self.rating,
+            _ => 0,
+        }
+    }
+
+    pub fn armor(&self) -> i32 {
+        match self.ware {
+            Cyberware::DermalPlating => self.rating,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Implant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.ware)?;
+        if self.ware.max_rating() > 1 {
+            write!(f, " {}", self.rating)?;
+        }
+        if self.grade != Grade::Standard {
+            write!(f, " ({:?})", self.grade)?;
+        }
+        write!(f, ", Essence {}", format_essence(self.essence_cost()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CyberError {
+    BadRating(Cyberware, i32),
+    AlreadyInstalled(Cyberware),
+    /// Carries the Essence the character has left.
+    NotEnoughEssence(Essence),
+}
+
+impl fmt::Display for CyberError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CyberError::BadRating(ware, rating) =>
+                write!(f, "{:?} doesn't come in rating {}", ware, rating),
+            CyberError::AlreadyInstalled(ware) =>
+                write!(f, "{:?} is already installed", ware),
+            CyberError::NotEnoughEssence(left) =>
+                write!(f, "that would leave no Essence (only {} left)",
+                       format_essence(left)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::Attribute;
+    use cyberware::{format_essence, CyberError, Cyberware, Grade, Implant};
+
+    #[test]
+    fn test_essence_costs() {
+        let wired = |rating, grade| Implant::new(Cyberware::WiredReflexes, rating, grade)
+            .unwrap().essence_cost();
+        assert_eq!(wired(1, Grade::Standard), 200);
+        assert_eq!(wired(2, Grade::Alpha), 240);
+        assert_eq!(wired(3, Grade::Delta), 250);
+        let smartlink = Implant::new(Cyberware::Smartlink, 1, Grade::Beta).unwrap();
+        assert_eq!(smartlink.essence_cost(), 30);
+        assert_eq!(format_essence(600 - 230), "3.70");
+        assert_eq!(format_essence(5), "0.05");
+    }
+
+    #[test]
+    fn test_ratings() {
+        assert_eq!(Implant::new(Cyberware::Smartlink, 2, Grade::Standard),
+                   Err(CyberError::BadRating(Cyberware::Smartlink, 2)));
+        assert_eq!(Implant::new(Cyberware::DermalPlating, 0, Grade::Standard),
+                   Err(CyberError::BadRating(Cyberware::DermalPlating, 0)));
+        let plating = Implant::new(Cyberware::DermalPlating, 2, Grade::Standard).unwrap();
+        assert_eq!(plating.armor(), 2);
+        assert_eq!(plating.to_string(), "DermalPlating 2, Essence 1.00");
+        let arm = Implant::new(Cyberware::Cyberarm, 1, Grade::Alpha).unwrap();
+        assert_eq!(arm.attr_bonus(Attribute::Strength), 1);
+        assert_eq!(arm.attr_bonus(Attribute::Body), 0);
+        assert_eq!(arm.to_string(), "Cyberarm (Alpha), Essence 0.80");
+    }
+}
@@ -1,86 +1,372 @@
 extern crate rand;
-use self::rand::Rng;
+use std::cell::RefCell;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+use self::rand::{Rng, SeedableRng, StdRng, XorShiftRng};
+use events::{Event, EventSink, StdoutSink};
+use skills::Defaulting;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct RollResult {
     pub success: bool,
     pub catastrophic_fail: bool,
     pub successes: i32,
+    pub dice: Vec<Die>,
+    pub target: i32,
+    /// Karma Pool spent on this roll after the fact, in order.
+    pub karma: Vec<KarmaSpend>,
+    /// Set when a skill test fell back on a related skill or attribute.
+    pub defaulted: Option<Defaulting>,
+}
+
+impl RollResult {
+    /// Tallies successes against `target`, following the Rule of Six for any
+    /// die that exploded.
+    pub fn from_dice(dice: Vec<Die>, target: i32) -> RollResult {
+        let successes = dice.iter().filter(|d| d.total() > target).count() as i32;
+        RollResult {
+            success: successes > 0,
+            catastrophic_fail: dice.iter().all(|d| d.total() == 1),
+            successes,
+            dice,
+            target,
+            karma: Vec::new(),
+            defaulted: None,
+        }
+    }
+
+    /// Rolls every die that didn't beat the target again, keeping the rest,
+    /// and returns how many were rerolled. Successes bought and disasters
+    /// avoided before the reroll still stand.
+    pub fn reroll_failures<R: Roller>(&mut self, roller: &R) -> i32 {
+        let target = self.target;
+        let mut rerolled = 0;
+        let dice = self.dice.drain(..).map(|d| if d.total() > target { d } else {
+            rerolled += 1;
+            roller.roll_die(true)
+        }).collect();
+        let karma = mem::take(&mut self.karma);
+        let defaulted = self.defaulted.take();
+        *self = RollResult { defaulted, ..RollResult::from_dice(dice, target) };
+        for spend in &karma {
+            match *spend {
+                KarmaSpend::BoughtSuccess => self.successes += 1,
+                KarmaSpend::AvoidedDisaster => self.catastrophic_fail = false,
+                KarmaSpend::Reroll { .. } => (),
+            }
+        }
+        self.karma = karma;
+        rerolled
+    }
+
+    /// How many times the failures have been rerolled already.
+    pub fn rerolls(&self) -> i32 {
+        self.karma.iter().filter(|k| matches!(**k, KarmaSpend::Reroll { .. })).count() as i32
+    }
+}
+
+/// What a point or more of Karma Pool bought on a roll.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KarmaSpend {
+    /// Rerolled `dice` failures for `cost` points.
+    Reroll { dice: i32, cost: i32 },
+    /// Turned a catastrophic failure into a plain one.
+    AvoidedDisaster,
+    /// Bought one success outright, for a point gone for good.
+    BoughtSuccess,
+}
+
+impl fmt::Display for KarmaSpend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KarmaSpend::Reroll { dice, cost } =>
+                write!(f, "rerolled {} for {} karma", dice, cost),
+            KarmaSpend::AvoidedDisaster => write!(f, "avoided disaster"),
+            KarmaSpend::BoughtSuccess => write!(f, "bought a success"),
+        }
+    }
+}
+
+impl fmt::Display for RollResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} vs TN {} \u{2192} {} success{}",
+               format_dice(&self.dice), self.target, self.successes,
+               if self.successes == 1 { "" } else { "es" })?;
+        if self.catastrophic_fail {
+            write!(f, " (catastrophic failure)")?;
+        }
+        if let Some(ref d) = self.defaulted {
+            write!(f, " (defaulted to {})", d)?;
+        }
+        if !self.karma.is_empty() {
+            let spent: Vec<String> = self.karma.iter().map(|k| k.to_string()).collect();
+            write!(f, "; {}", spent.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A single die as it was rolled: one face, or every face of its explosion
+/// chain if it kept coming up 6.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Die {
+    pub faces: Vec<i32>,
+}
+
+impl Die {
+    pub fn total(&self) -> i32 {
+        self.faces.iter().sum()
+    }
+}
+
+impl fmt::Display for Die {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let faces: Vec<String> = self.faces.iter().map(|f| f.to_string()).collect();
+        write!(f, "{}", faces.join("+"))
+    }
+}
+
+/// Renders dice the way they'd be read out at the table: `[5, 6+6+2, 1]`.
+pub fn format_dice(dice: &[Die]) -> String {
+    let dice: Vec<String> = dice.iter().map(|d| d.to_string()).collect();
+    format!("[{}]", dice.join(", "))
 }
 
 pub trait Roller {
     fn new(verbose: bool) -> Self;
     fn verbose(&self) -> bool;
 
+    /// Reports what the roller is up to. Rollers only speak up when verbose,
+    /// and by default they do it on stdout.
+    fn emit(&self, event: Event) {
+        if self.verbose() {
+            StdoutSink.emit(&event);
+        }
+    }
+
     fn nd6(&self, rolls: i32) -> i32 {
         let mut result = 0;
         for _ in 0..rolls {
-            result = result + self.d6();
+            result += self.d6();
         }
         result
     }
 
-    fn explode(&self) -> i32 {
-        if self.verbose() {
-            println!("explosion!");
-        }
-        let mut next_roll = self.d6();
-        let mut result = 6 + next_roll;
-        while next_roll == 6 {
-            if self.verbose() {
-                println!("explosion!");
-            }
-            next_roll = self.d6();
-            result += next_roll;
+    fn d6(&self) -> i32;
+
+    fn roll_die(&self, exploding: bool) -> Die {
+        let mut faces = vec![self.d6()];
+        while exploding && faces[faces.len() - 1] == 6 {
+            self.emit(Event::Exploded);
+            faces.push(self.d6());
         }
-        result
+        Die { faces }
     }
 
-    fn d6(&self) -> i32 {
-        // TODO use Range
-        rand::thread_rng().gen_range(1,7)
+    fn roll(&self, rolls: i32, target: i32) -> RollResult {
+        self.emit(Event::Rolling { dice: rolls, target });
+        let dice = (0..rolls).map(|_| self.roll_die(true)).collect();
+        RollResult::from_dice(dice, target)
     }
+}
 
-    fn roll(&self, rolls: i32, target: i32) -> RollResult {
-        if self.verbose() {
-            println!("Rolling {}d6 with target number {}", rolls, target);
-        }
-        let mut successes = 0;
-        let mut ones = 0;
-        for _ in 0..rolls {
-            let mut result = self.d6();
-            if result == 6 {
-                result = self.explode();
-            }
+#[derive(Debug)]
+pub struct DefaultRoller {
+    verbose: bool,
+    rng: RefCell<StdRng>,
+    sink: Rc<dyn EventSink>,
+}
 
-            if result == 1 {
-                ones += 1;
-            }
+impl DefaultRoller {
+    pub fn set_sink(&mut self, sink: Rc<dyn EventSink>) {
+        self.sink = sink
+    }
+}
 
-            if result > target {
-                successes += 1;
-            }
+impl Roller for DefaultRoller {
+    fn new(verbose: bool) -> Self {
+        DefaultRoller {
+            verbose,
+            rng: RefCell::new(StdRng::new().expect("could not seed dice from the OS")),
+            sink: Rc::new(StdoutSink),
         }
+    }
 
-        RollResult {
-            success: successes > 0,
-            catastrophic_fail: ones == rolls,
-            successes: successes,
+    fn verbose(&self) -> bool {self.verbose}
+
+    fn emit(&self, event: Event) {
+        if self.verbose {
+            self.sink.emit(&event);
         }
     }
+
+    fn d6(&self) -> i32 {
+        self.rng.borrow_mut().gen_range(1, 7)
+    }
 }
 
+/// A roller whose dice are entirely determined by its seed, so a session
+/// can be replayed roll for roll.
 #[derive(Debug)]
-pub struct DefaultRoller {
+pub struct SeededRoller {
     verbose: bool,
+    seed: u64,
+    rng: RefCell<XorShiftRng>,
+    sink: Rc<dyn EventSink>,
 }
 
-impl Roller for DefaultRoller {
+impl SeededRoller {
+    pub fn with_seed(verbose: bool, seed: u64) -> Self {
+        SeededRoller {
+            verbose,
+            seed,
+            rng: RefCell::new(XorShiftRng::from_seed(expand_seed(seed))),
+            sink: Rc::new(StdoutSink),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_sink(&mut self, sink: Rc<dyn EventSink>) {
+        self.sink = sink
+    }
+}
+
+impl Roller for SeededRoller {
+    /// Picks a fresh seed; use `seed()` to find out what it was.
     fn new(verbose: bool) -> Self {
-        DefaultRoller {
-            verbose: verbose
+        SeededRoller::with_seed(verbose, rand::random())
+    }
+
+    fn verbose(&self) -> bool {self.verbose}
+
+    fn emit(&self, event: Event) {
+        if self.verbose {
+            self.sink.emit(&event);
         }
     }
 
-    fn verbose(&self) -> bool {return self.verbose}
+    fn d6(&self) -> i32 {
+        self.rng.borrow_mut().gen_range(1, 7)
+    }
+}
+
+// XorShift wants 128 bits of seed that aren't all zero, so spread the u64
+// out with splitmix64, which never yields four zero words.
+fn expand_seed(seed: u64) -> [u32; 4] {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    let a = next();
+    let b = next();
+    [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32]
+}
+
+/// Hands out a fixed sequence of faces, for tests anywhere in the crate.
+#[cfg(test)]
+pub struct ScriptedRoller {
+    faces: RefCell<Vec<i32>>,
+}
+
+#[cfg(test)]
+impl ScriptedRoller {
+    pub fn with(faces: &[i32]) -> ScriptedRoller {
+        let mut faces = faces.to_vec();
+        faces.reverse();
+        ScriptedRoller { faces: RefCell::new(faces) }
+    }
+}
+
+#[cfg(test)]
+impl Roller for ScriptedRoller {
+    fn new(_: bool) -> Self { ScriptedRoller::with(&[]) }
+    fn verbose(&self) -> bool { false }
+    fn d6(&self) -> i32 { self.faces.borrow_mut().pop().expect("out of faces") }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+    use dice::{Die, Roller, RollResult, ScriptedRoller, SeededRoller};
+    use events::{Event, EventLog};
+
+    #[test]
+    fn test_roll_keeps_every_die() {
+        let roller = ScriptedRoller::with(&[5, 6, 6, 2, 1, 3]);
+        let result = roller.roll(4, 4);
+        assert_eq!(result.dice, vec![Die { faces: vec![5] },
+                                     Die { faces: vec![6, 6, 2] },
+                                     Die { faces: vec![1] },
+                                     Die { faces: vec![3] }]);
+        assert_eq!(result.successes, 2);
+        assert!(result.success);
+        assert!(!result.catastrophic_fail);
+        assert_eq!(result.to_string(), "[5, 6+6+2, 1, 3] vs TN 4 \u{2192} 2 successes");
+    }
+
+    #[test]
+    fn test_roll_events() {
+        let log = Rc::new(EventLog::new());
+        let mut quiet = SeededRoller::with_seed(false, 6);
+        quiet.set_sink(log.clone());
+        quiet.roll(20, 4);
+        assert!(log.take().is_empty());
+
+        let mut loud = SeededRoller::with_seed(true, 6);
+        loud.set_sink(log.clone());
+        let result = loud.roll(20, 4);
+        let explosions = result.dice.iter().map(|d| d.faces.len() - 1).sum();
+        let events = log.take();
+        assert_eq!(events[0], Event::Rolling { dice: 20, target: 4 });
+        assert_eq!(events.len() - 1, explosions);
+        assert!(events[1..].iter().all(|e| *e == Event::Exploded));
+    }
+
+    #[test]
+    fn test_catastrophic_fail() {
+        let result = RollResult::from_dice(vec![Die { faces: vec![1] },
+                                                Die { faces: vec![1] }], 3);
+        assert!(!result.success);
+        assert!(result.catastrophic_fail);
+        assert_eq!(result.to_string(),
+                   "[1, 1] vs TN 3 \u{2192} 0 successes (catastrophic failure)");
+
+        let result = RollResult::from_dice(vec![Die { faces: vec![1] },
+                                                Die { faces: vec![4] }], 3);
+        assert_eq!(result.to_string(), "[1, 4] vs TN 3 \u{2192} 1 success");
+    }
+
+    #[test]
+    fn test_seeded_rolls_repeat() {
+        let a = SeededRoller::with_seed(false, 1234);
+        let b = SeededRoller::with_seed(false, 1234);
+        for _ in 0..20 {
+            assert_eq!(a.roll(6, 4), b.roll(6, 4));
+        }
+    }
+
+    #[test]
+    fn test_seeds_differ() {
+        let a = SeededRoller::with_seed(false, 1);
+        let b = SeededRoller::with_seed(false, 2);
+        let a_rolls: Vec<i32> = (0..20).map(|_| a.d6()).collect();
+        let b_rolls: Vec<i32> = (0..20).map(|_| b.d6()).collect();
+        assert!(a_rolls != b_rolls);
+        assert!(a_rolls.iter().all(|r| (1..=6).contains(r)));
+    }
+
+    #[test]
+    fn test_random_seed_is_reported() {
+        let a = SeededRoller::new(false);
+        let b = SeededRoller::with_seed(false, a.seed());
+        assert_eq!(a.nd6(10), b.nd6(10));
+    }
 }
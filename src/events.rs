@@ -0,0 +1,95 @@
+use std::cell::RefCell;
+use std::fmt;
+
+use common::{DamageLevel, DamageType, TargetNumber};
+use magic::SpellName;
+
+/// Something noteworthy that happened while rolling or keeping track of a
+/// character.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Rolling { dice: i32, target: TargetNumber },
+    Exploded,
+    KnockedOut { name: String },
+    Died { name: String },
+    DrainTaken { name: String, level: DamageLevel, kind: DamageType },
+    RolledWhileIncapacitated { name: String },
+    SpellDropped { name: String, spell: SpellName },
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Event::Rolling { dice, target } =>
+                write!(f, "Rolling {}d6 with target number {}", dice, target),
+            Event::Exploded =>
+                write!(f, "explosion!"),
+            Event::KnockedOut { ref name } =>
+                write!(f, "WARNING: {} has fallen unconscious.", name),
+            Event::Died { ref name } =>
+                write!(f, "WARNING: {} has died.", name),
+            Event::DrainTaken { ref name, level, ref kind } =>
+                write!(f, "{} takes {:?} {:?} drain.", name, level, kind),
+            Event::RolledWhileIncapacitated { .. } =>
+                write!(f, "WARNING rolling for dead or unconscious character"),
+            Event::SpellDropped { ref name, ref spell } =>
+                write!(f, "{} can no longer sustain {}.", name, spell),
+        }
+    }
+}
+
+/// Receives events as they happen.
+pub trait EventSink {
+    fn emit(&self, event: &Event);
+}
+
+impl fmt::Debug for dyn EventSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EventSink")
+    }
+}
+
+/// Prints events to stdout the way shadowfun always has.
+#[derive(Debug)]
+pub struct StdoutSink;
+
+impl EventSink for StdoutSink {
+    fn emit(&self, event: &Event) {
+        match *event {
+            // Drain has never been announced; callers get it from SpellResult.
+            Event::DrainTaken { .. } => (),
+            _ => println!("{}", event),
+        }
+    }
+}
+
+/// Drops everything on the floor.
+#[derive(Debug)]
+pub struct NullSink;
+
+impl EventSink for NullSink {
+    fn emit(&self, _: &Event) {}
+}
+
+/// Keeps events around until someone comes to collect them.
+#[derive(Debug, Default)]
+pub struct EventLog {
+    events: RefCell<Vec<Event>>,
+}
+
+impl EventLog {
+    pub fn new() -> EventLog {
+        EventLog::default()
+    }
+
+    /// Hands over everything logged so far, leaving the log empty.
+    pub fn take(&self) -> Vec<Event> {
+        self.events.borrow_mut().drain(..).collect()
+    }
+}
+
+impl EventSink for EventLog {
+    fn emit(&self, event: &Event) {
+        self.events.borrow_mut().push(event.clone());
+    }
+}
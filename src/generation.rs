@@ -0,0 +1,453 @@
+//! Priority-based character generation: pick A–E for metatype, magic,
+//! attributes, skills and resources, spend the points, and get a runner
+//! that's legal from the start.
+use std::collections::HashMap;
+use std::cmp::max;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use toml;
+
+use character::{Character, Race};
+use common::Attribute;
+
+/// The highest any attribute or skill can be bought at during generation.
+pub const MAX_RATING: i32 = 6;
+
+/// The Magic rating of anyone who spends priority A or B on being awakened.
+pub const AWAKENED_MAGIC: i32 = 6;
+
+const ATTRIBUTES: [Attribute; 6] = [
+    Attribute::Body,
+    Attribute::Quickness,
+    Attribute::Strength,
+    Attribute::Charisma,
+    Attribute::Intelligence,
+    Attribute::Willpower,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Priority {
+    A,
+    B,
+    C,
+    D,
+    E,
+}
+
+impl FromStr for Priority {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Priority, ()> {
+        match s.to_uppercase().as_str() {
+            "A" => Ok(Priority::A),
+            "B" => Ok(Priority::B),
+            "C" => Ok(Priority::C),
+            "D" => Ok(Priority::D),
+            "E" => Ok(Priority::E),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Priority {
+    pub fn attribute_points(self) -> i32 {
+        match self {
+            Priority::A => 30,
+            Priority::B => 27,
+            Priority::C => 24,
+            Priority::D => 21,
+            Priority::E => 18,
+        }
+    }
+
+    pub fn skill_points(self) -> i32 {
+        match self {
+            Priority::A => 50,
+            Priority::B => 40,
+            Priority::C => 34,
+            Priority::D => 30,
+            Priority::E => 27,
+        }
+    }
+
+    pub fn nuyen(self) -> i32 {
+        match self {
+            Priority::A => 1_000_000,
+            Priority::B => 400_000,
+            Priority::C => 90_000,
+            Priority::D => 20_000,
+            Priority::E => 5_000,
+        }
+    }
+
+    /// A is a full magician, B an adept; anything lower is mundane.
+    pub fn magic(self) -> i32 {
+        match self {
+            Priority::A | Priority::B => AWAKENED_MAGIC,
+            _ => 0,
+        }
+    }
+
+    /// The lowest metatype priority that can buy `race`.
+    pub fn needed_for(race: Race) -> Priority {
+        match race {
+            Race::Troll | Race::Elf => Priority::C,
+            Race::Dwarf | Race::Ork => Priority::D,
+            Race::Human => Priority::E,
+        }
+    }
+}
+
+/// Which priority went to which column. Each letter is used exactly once.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Priorities {
+    pub metatype: Priority,
+    pub magic: Priority,
+    pub attributes: Priority,
+    pub skills: Priority,
+    pub resources: Priority,
+}
+
+/// Reads five letters in column order, e.g. `DABCE` for metatype D, magic A,
+/// attributes B, skills C and resources E.
+impl FromStr for Priorities {
+    type Err = GenError;
+
+    fn from_str(letters: &str) -> Result<Priorities, GenError> {
+        let bad = || GenError::BadPriorities(letters.to_owned());
+        let ps = letters.chars()
+            .map(|c| c.to_string().parse::<Priority>().map_err(|_| bad()))
+            .collect::<Result<Vec<_>, _>>()?;
+        if ps.len() != 5 {
+            return Err(bad());
+        }
+        Ok(Priorities {
+            metatype: ps[0],
+            magic: ps[1],
+            attributes: ps[2],
+            skills: ps[3],
+            resources: ps[4],
+        })
+    }
+}
+
+impl Priorities {
+    fn validate(&self, race: Race) -> Result<(), GenError> {
+        let mut used = [self.metatype, self.magic, self.attributes,
+                            self.skills, self.resources];
+        used.sort();
+        for pair in used.windows(2) {
+            if pair[0] == pair[1] {
+                return Err(GenError::RepeatedPriority(pair[0]));
+            }
+        }
+        let needed = Priority::needed_for(race);
+        if self.metatype > needed {
+            return Err(GenError::MetatypeTooLow(race, needed));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum GenError {
+    BadPriorities(String),
+    RepeatedPriority(Priority),
+    /// Carries the priority the metatype needs.
+    MetatypeTooLow(Race, Priority),
+    UnknownAttribute(String),
+    AttributeOutOfRange(Attribute, i32),
+    SkillOutOfRange(String, i32),
+    /// Carries how many points are left.
+    OutOfAttributePoints(i32),
+    OutOfSkillPoints(i32),
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for GenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GenError::BadPriorities(ref p) =>
+                write!(f, "'{}' isn't five priorities, e.g. DABCE", p),
+            GenError::RepeatedPriority(p) =>
+                write!(f, "priority {:?} is used more than once", p),
+            GenError::MetatypeTooLow(race, p) =>
+                write!(f, "{:?} needs metatype priority {:?} or better", race, p),
+            GenError::UnknownAttribute(ref a) =>
+                write!(f, "no attribute named '{}'", a),
+            GenError::AttributeOutOfRange(attr, v) =>
+                write!(f, "{:?} {} must be between 1 and {}", attr, v, MAX_RATING),
+            GenError::SkillOutOfRange(ref s, v) =>
+                write!(f, "{} {} must be between 1 and {}", s, v, MAX_RATING),
+            GenError::OutOfAttributePoints(left) =>
+                write!(f, "only {} attribute points left", left),
+            GenError::OutOfSkillPoints(left) =>
+                write!(f, "only {} skill points left", left),
+            GenError::Io(ref e) => write!(f, "{}", e),
+            GenError::Parse(ref e) => write!(f, "bad character spec: {}", e),
+        }
+    }
+}
+
+impl Error for GenError {}
+
+impl From<io::Error> for GenError {
+    fn from(e: io::Error) -> GenError {
+        GenError::Io(e)
+    }
+}
+
+/// A character part way through generation. Attributes are bought at their
+/// base ratings, before metatype modifiers, and start at 1.
+#[derive(Debug)]
+pub struct Builder {
+    name: String,
+    race: Race,
+    priorities: Priorities,
+    attributes: Vec<(Attribute, i32)>,
+    skills: Vec<(String, i32)>,
+}
+
+impl Builder {
+    pub fn new(name: &str, race: Race, priorities: Priorities) -> Result<Builder, GenError> {
+        priorities.validate(race)?;
+        Ok(Builder {
+            name: name.to_owned(),
+            race,
+            priorities,
+            attributes: ATTRIBUTES.iter().map(|&a| (a, 1)).collect(),
+            skills: Vec::new(),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn attribute_points_left(&self) -> i32 {
+        let spent: i32 = self.attributes.iter().map(|&(_, v)| v).sum();
+        self.priorities.attributes.attribute_points() - spent
+    }
+
+    pub fn skill_points_left(&self) -> i32 {
+        let spent: i32 = self.skills.iter().map(|&(_, v)| v).sum();
+        self.priorities.skills.skill_points() - spent
+    }
+
+    /// Buys `attr` at a base rating of `rating`, refunding whatever it was
+    /// bought at before.
+    pub fn buy_attribute(&mut self, attr: Attribute, rating: i32) -> Result<(), GenError> {
+        if !(1..=MAX_RATING).contains(&rating) {
+            return Err(GenError::AttributeOutOfRange(attr, rating));
+        }
+        let left = self.attribute_points_left();
+        let slot = self.attributes.iter_mut().find(|&&mut (a, _)| a == attr)
+            .expect("every attribute starts out bought");
+        if rating - slot.1 > left {
+            return Err(GenError::OutOfAttributePoints(left));
+        }
+        slot.1 = rating;
+        Ok(())
+    }
+
+    /// Buys `skill` at `rating`, refunding whatever it was bought at before.
+    pub fn buy_skill(&mut self, skill: &str, rating: i32) -> Result<(), GenError> {
+        let skill = skill.to_lowercase();
+        if !(1..=MAX_RATING).contains(&rating) {
+            return Err(GenError::SkillOutOfRange(skill, rating));
+        }
+        let left = self.skill_points_left();
+        let current = self.skills.iter().position(|s| s.0 == skill);
+        let refund = current.map_or(0, |i| self.skills[i].1);
+        if rating - refund > left {
+            return Err(GenError::OutOfSkillPoints(left));
+        }
+        match current {
+            Some(i) => self.skills[i].1 = rating,
+            None => self.skills.push((skill, rating)),
+        }
+        Ok(())
+    }
+
+    /// The finished character, metatype modifiers and all.
+    pub fn build(self) -> Character {
+        let mut c = Character::new(&self.name, self.race);
+        for &(attr, base) in &self.attributes {
+            let rating = max(1, base + self.race.modifier(attr));
+            c.set_attr(attr, rating).expect("bases are capped below racial limits");
+        }
+        for &(ref skill, rating) in &self.skills {
+            c.learn_skill(skill);
+            c.improve_skill_by(skill, rating - 1);
+        }
+        c.set_magic(self.priorities.magic.magic());
+        c.set_nuyen(self.priorities.resources.nuyen());
+        c
+    }
+}
+
+impl fmt::Display for Builder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({:?})", self.name, self.race)?;
+        for (&(attr, base), letter) in self.attributes.iter().zip("BQSCIW".chars()) {
+            write!(f, " {}{}", letter, max(1, base + self.race.modifier(attr)))?;
+        }
+        write!(f, " | {} attribute and {} skill points left",
+               self.attribute_points_left(), self.skill_points_left())
+    }
+}
+
+/// A whole character written down ahead of time, e.g.
+///
+/// ```toml
+/// name = "jill"
+/// race = "Ork"
+///
+/// [priorities]
+/// metatype = "D"
+/// magic = "A"
+/// attributes = "B"
+/// skills = "C"
+/// resources = "E"
+///
+/// [attributes]
+/// willpower = 6
+///
+/// [skills]
+/// sorcery = 6
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct CharacterSpec {
+    pub name: String,
+    pub race: Race,
+    pub priorities: Priorities,
+    #[serde(default)]
+    pub attributes: HashMap<String, i32>,
+    #[serde(default)]
+    pub skills: HashMap<String, i32>,
+}
+
+impl CharacterSpec {
+    pub fn from_toml(text: &str) -> Result<CharacterSpec, GenError> {
+        toml::from_str(text).map_err(GenError::Parse)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<CharacterSpec, GenError> {
+        CharacterSpec::from_toml(&fs::read_to_string(path)?)
+    }
+
+    pub fn build(&self) -> Result<Character, GenError> {
+        let mut builder = Builder::new(&self.name, self.race, self.priorities)?;
+        for (name, &rating) in &self.attributes {
+            let attr = name.parse()
+                .map_err(|_| GenError::UnknownAttribute(name.clone()))?;
+            builder.buy_attribute(attr, rating)?;
+        }
+        for (skill, &rating) in &self.skills {
+            builder.buy_skill(skill, rating)?;
+        }
+        Ok(builder.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use character::Race;
+    use common::{Attribute, HasAttrs};
+    use generation::{Builder, CharacterSpec, GenError, Priorities, Priority};
+
+    #[test]
+    fn test_priorities() {
+        let p = "dabce".parse::<Priorities>().unwrap();
+        assert_eq!(p.metatype, Priority::D);
+        assert_eq!(p.resources, Priority::E);
+        assert!(matches!("DABC".parse::<Priorities>(),
+                         Err(GenError::BadPriorities(_))));
+        assert!(matches!("DABCF".parse::<Priorities>(),
+                         Err(GenError::BadPriorities(_))));
+        let build = |race, ps: &str| Builder::new("x", race, ps.parse().unwrap());
+        assert!(matches!(build(Race::Human, "AABCE"),
+                         Err(GenError::RepeatedPriority(Priority::A))));
+        assert!(matches!(build(Race::Troll, "DABCE"),
+                         Err(GenError::MetatypeTooLow(Race::Troll, Priority::C))));
+        assert!(build(Race::Human, "DABCE").is_ok());
+    }
+
+    #[test]
+    fn test_spending_points() {
+        let p = "CEDAB".parse::<Priorities>().unwrap();
+        let mut b = Builder::new("acid", Race::Troll, p).unwrap();
+        assert_eq!(b.attribute_points_left(), 21 - 6);
+        b.buy_attribute(Attribute::Body, 6).unwrap();
+        b.buy_attribute(Attribute::Strength, 6).unwrap();
+        b.buy_attribute(Attribute::Quickness, 6).unwrap();
+        assert_eq!(b.attribute_points_left(), 0);
+        assert!(matches!(b.buy_attribute(Attribute::Willpower, 2),
+                         Err(GenError::OutOfAttributePoints(0))));
+        // Lowering one attribute frees points for another.
+        b.buy_attribute(Attribute::Quickness, 5).unwrap();
+        b.buy_attribute(Attribute::Willpower, 2).unwrap();
+        assert!(matches!(b.buy_attribute(Attribute::Body, 7),
+                         Err(GenError::AttributeOutOfRange(Attribute::Body, 7))));
+
+        b.buy_skill("Unarmed Combat", 6).unwrap();
+        b.buy_skill("unarmed combat", 5).unwrap();
+        assert_eq!(b.skill_points_left(), 45);
+        assert!(matches!(b.buy_skill("firearms", 0),
+                         Err(GenError::SkillOutOfRange(..))));
+
+        let c = b.build();
+        assert_eq!(c.attr(Attribute::Body), 11);
+        assert_eq!(c.attr(Attribute::Strength), 10);
+        assert_eq!(c.attr(Attribute::Quickness), 4);
+        assert_eq!(c.attr(Attribute::Intelligence), 1);
+        assert_eq!(c.skill("unarmed combat"), 5);
+        assert_eq!(c.magic(), 0);
+        assert_eq!(c.nuyen(), 400_000);
+    }
+
+    #[test]
+    fn test_spec() {
+        let spec = CharacterSpec::from_toml(r#"
+            name = "jill"
+            race = "Ork"
+
+            [priorities]
+            metatype = "D"
+            magic = "A"
+            attributes = "B"
+            skills = "C"
+            resources = "E"
+
+            [attributes]
+            willpower = 6
+            body = 3
+
+            [skills]
+            sorcery = 6
+            conjuring = 4
+        "#).unwrap();
+        let jill = spec.build().unwrap();
+        assert_eq!(jill.name(), "jill");
+        assert_eq!(jill.attr(Attribute::Willpower), 6);
+        assert_eq!(jill.attr(Attribute::Body), 6);
+        assert_eq!(jill.attr(Attribute::Charisma), 1);
+        assert_eq!(jill.skill("sorcery"), 6);
+        assert_eq!(jill.magic(), 6);
+        assert_eq!(jill.nuyen(), 5_000);
+
+        let spec = CharacterSpec::from_toml(r#"
+            name = "jill"
+            race = "Ork"
+            priorities = { metatype = "D", magic = "A", attributes = "B", skills = "C", resources = "E" }
+            attributes = { luck = 3 }
+        "#).unwrap();
+        assert!(matches!(spec.build(), Err(GenError::UnknownAttribute(_))));
+    }
+}
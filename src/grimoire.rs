@@ -0,0 +1,144 @@
+//! The spells the table plays with. They live in a data file rather than in
+//! code; `Grimoire::core` is the one that ships with shadowfun.
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use toml;
+
+use magic::KnownSpell;
+
+const CORE: &str = include_str!("../data/grimoire.toml");
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Grimoire {
+    #[serde(rename = "spell", default)]
+    pub spells: Vec<KnownSpell>,
+}
+
+#[derive(Debug)]
+pub enum GrimoireError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for GrimoireError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GrimoireError::Io(ref e) => write!(f, "{}", e),
+            GrimoireError::Parse(ref e) => write!(f, "bad grimoire: {}", e),
+        }
+    }
+}
+
+impl Error for GrimoireError {}
+
+impl From<io::Error> for GrimoireError {
+    fn from(e: io::Error) -> GrimoireError {
+        GrimoireError::Io(e)
+    }
+}
+
+impl Grimoire {
+    pub fn core() -> Grimoire {
+        Grimoire::from_toml(CORE).expect("the core grimoire should parse")
+    }
+
+    pub fn from_toml(text: &str) -> Result<Grimoire, GrimoireError> {
+        toml::from_str(text).map_err(GrimoireError::Parse)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Grimoire, GrimoireError> {
+        Grimoire::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// The spell called `name`, ignoring case.
+    pub fn find(&self, name: &str) -> Option<&KnownSpell> {
+        self.spells.iter().find(|s| s.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Adds `spell`, replacing any spell of the same name.
+    pub fn add(&mut self, spell: KnownSpell) {
+        match self.spells.iter().position(|s| s.name().eq_ignore_ascii_case(spell.name())) {
+            Some(i) => self.spells[i] = spell,
+            None => self.spells.push(spell),
+        }
+    }
+
+    /// The spells `base` lacks or has differently.
+    pub fn changes_from(&self, base: &Grimoire) -> Vec<&KnownSpell> {
+        self.spells.iter().filter(|s| base.find(s.name()) != Some(*s)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{Attribute, DamageLevel, DamageType};
+    use grimoire::{Grimoire, GrimoireError};
+    use magic::{Category, Drain, Duration, KnownSpell, SpellDamage, SpellRange, SpellType};
+
+    #[test]
+    fn test_core_grimoire() {
+        let core = Grimoire::core();
+        for category in &[Category::Combat, Category::Detection, Category::Health,
+                          Category::Illusion, Category::Manipulation] {
+            assert!(core.spells.iter().any(|s| s.category() == *category));
+        }
+        match *core.find("Manabolt").unwrap() {
+            KnownSpell::Resisted(ref s) => {
+                assert_eq!(s.name, "manabolt");
+                assert_eq!(s.kind, SpellType::Mana);
+                assert_eq!(s.duration, Duration::Instant);
+                assert_eq!(s.range, SpellRange::LineOfSight);
+                assert_eq!(s.drain, Drain::new(0, DamageLevel::Moderate));
+                assert_eq!(s.target, Attribute::Willpower);
+                assert_eq!(s.damage, Some(SpellDamage::new(DamageLevel::Moderate,
+                                                           DamageType::Physical)));
+            },
+            ref other => panic!("expected a resisted spell, got {:?}", other),
+        }
+        assert_eq!(core.find("oxygenate").unwrap().to_string(),
+                   "oxygenate: Health, Mana, Sustained, Touch, drain (F/2)+2 L, TN 4");
+        assert!(core.find("fireball").is_none());
+    }
+
+    #[test]
+    fn test_custom_grimoire() {
+        let grimoire = Grimoire::from_toml(r#"
+            [[spell]]
+            name = "wreck"
+            category = "Manipulation"
+            type = "Physical"
+            duration = "Instant"
+            range = "Touch"
+            drain = "(F/2)+3 D"
+            target = 6
+        "#).unwrap();
+        assert_eq!(grimoire.spells.len(), 1);
+        assert_eq!(grimoire.find("wreck").unwrap().category(), Category::Manipulation);
+
+        let mut core = Grimoire::core();
+        let count = core.spells.len();
+        assert!(core.changes_from(&Grimoire::core()).is_empty());
+        core.add(grimoire.spells[0].clone());
+        core.add(grimoire.spells[0].clone());
+        assert_eq!(core.spells.len(), count + 1);
+        assert_eq!(core.changes_from(&Grimoire::core()), vec![&grimoire.spells[0]]);
+
+        let bad = Grimoire::from_toml(r#"
+            [[spell]]
+            name = "wreck"
+            category = "Manipulation"
+            type = "Physical"
+            duration = "Instant"
+            range = "Touch"
+            drain = "lots"
+            target = 6
+        "#);
+        assert!(matches!(bad, Err(GrimoireError::Parse(_))));
+        assert!(matches!(Grimoire::load("/nonexistent/grimoire.toml"),
+                         Err(GrimoireError::Io(_))));
+    }
+}
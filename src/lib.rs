@@ -0,0 +1,23 @@
+//! Shadowrun rules for the table: dice, characters, magic and the events
+//! they raise along the way.
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
+
+pub mod advancement;
+pub mod armor;
+pub mod common;
+pub mod dice;
+pub mod events;
+pub mod character;
+pub mod cyberware;
+pub mod magic;
+pub mod grimoire;
+pub mod notation;
+pub mod pools;
+pub mod skills;
+pub mod weapons;
+pub mod campaign;
+pub mod combat;
+pub mod generation;
@@ -1,7 +1,11 @@
-use common::{Attribute, DamageLevel, TargetNumber, HasAttrs};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use common::{Attribute, DamageLevel, DamageType, TargetNumber, HasAttrs};
 use dice::RollResult;
 
-pub type SpellName = &'static str;
+pub type SpellName = String;
 pub type ForceLevel = i32;
 
 pub trait SpellTargetNumber {
@@ -11,13 +15,13 @@ pub trait SpellTargetNumber {
 impl SpellTargetNumber for Attribute {
     fn to_tn<T:HasAttrs>(&self, spell_target:&T) -> TargetNumber {
         // TODO this seems dumb.
-        match self {
-            &Attribute::Willpower => spell_target.attr(Attribute::Willpower),
-            &Attribute::Strength => spell_target.attr(Attribute::Strength),
-            &Attribute::Intelligence => spell_target.attr(Attribute::Intelligence),
-            &Attribute::Charisma => spell_target.attr(Attribute::Charisma),
-            &Attribute::Quickness => spell_target.attr(Attribute::Quickness),
-            &Attribute::Body => spell_target.attr(Attribute::Body),
+        match *self {
+            Attribute::Willpower => spell_target.attr(Attribute::Willpower),
+            Attribute::Strength => spell_target.attr(Attribute::Strength),
+            Attribute::Intelligence => spell_target.attr(Attribute::Intelligence),
+            Attribute::Charisma => spell_target.attr(Attribute::Charisma),
+            Attribute::Quickness => spell_target.attr(Attribute::Quickness),
+            Attribute::Body => spell_target.attr(Attribute::Body),
         }
     }
 }
@@ -28,25 +32,281 @@ impl SpellTargetNumber for i32 {
     }
 }
 
-#[derive(Debug)]
+/// The five kinds of spell in the grimoire.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Category {
+    Combat,
+    Detection,
+    Health,
+    Illusion,
+    Manipulation,
+}
+
+impl FromStr for Category {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Category, ()> {
+        match s.to_lowercase().as_str() {
+            "combat" => Ok(Category::Combat),
+            "detection" => Ok(Category::Detection),
+            "health" => Ok(Category::Health),
+            "illusion" => Ok(Category::Illusion),
+            "manipulation" => Ok(Category::Manipulation),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Mana spells only touch living minds and bodies; physical spells work on
+/// anything, machines included.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SpellType {
+    Mana,
+    Physical,
+}
+
+impl SpellType {
+    /// What a target resists spells of this type with.
+    pub fn resisted_with(self) -> Attribute {
+        match self {
+            SpellType::Mana => Attribute::Willpower,
+            SpellType::Physical => Attribute::Body,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Duration {
+    Instant,
+    Sustained,
+    Permanent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SpellRange {
+    Touch,
+    LineOfSight,
+    /// Line of sight, hitting everyone in the area.
+    Area,
+}
+
+/// A drain code like `(F/2)+1 S`: the drain target number is half the force
+/// plus `modifier`, and `level` is what the caster takes before staging.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Drain {
+    pub modifier: i32,
+    pub level: DamageLevel,
+}
+
+impl Drain {
+    pub fn new(modifier: i32, level: DamageLevel) -> Drain {
+        Drain { modifier, level }
+    }
+
+    pub fn target_number(&self, force: ForceLevel) -> TargetNumber {
+        force / 2 + self.modifier
+    }
+}
+
+impl FromStr for Drain {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Drain, ()> {
+        let s = s.trim();
+        if !s.get(..5).is_some_and(|f| f.eq_ignore_ascii_case("(F/2)")) {
+            return Err(());
+        }
+        let rest = s[5..].trim();
+        let (modifier, level) = match rest.rfind(char::is_whitespace) {
+            Some(i) => (rest[..i].replace(' ', ""), rest[i..].trim()),
+            None => (String::new(), rest),
+        };
+        let modifier = if modifier.is_empty() {
+            0
+        } else {
+            modifier.parse().map_err(|_| ())?
+        };
+        let mut letters = level.chars();
+        let level = letters.next().and_then(DamageLevel::from_letter).ok_or(())?;
+        if letters.next().is_some() {
+            return Err(());
+        }
+        Ok(Drain { modifier, level })
+    }
+}
+
+impl TryFrom<String> for Drain {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Drain, String> {
+        s.parse().map_err(|_| format!("bad drain code '{}'", s))
+    }
+}
+
+impl From<Drain> for String {
+    fn from(drain: Drain) -> String {
+        drain.to_string()
+    }
+}
+
+impl fmt::Display for Drain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(F/2)")?;
+        if self.modifier != 0 {
+            write!(f, "{:+}", self.modifier)?;
+        }
+        write!(f, " {}", self.level.letter())
+    }
+}
+
+/// What a combat spell does to its target, written `M` or `S Stun`: the
+/// level before staging, and physical unless it says otherwise.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SpellDamage {
+    pub level: DamageLevel,
+    pub kind: DamageType,
+}
+
+impl SpellDamage {
+    pub fn new(level: DamageLevel, kind: DamageType) -> SpellDamage {
+        SpellDamage { level, kind }
+    }
+}
+
+impl FromStr for SpellDamage {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<SpellDamage, ()> {
+        let mut rest = s.trim().chars();
+        let level = rest.next().and_then(DamageLevel::from_letter).ok_or(())?;
+        let kind = match rest.as_str().trim() {
+            "" => DamageType::Physical,
+            kind => kind.parse()?,
+        };
+        Ok(SpellDamage { level, kind })
+    }
+}
+
+impl TryFrom<String> for SpellDamage {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<SpellDamage, String> {
+        s.parse().map_err(|_| format!("bad damage code '{}'", s))
+    }
+}
+
+impl From<SpellDamage> for String {
+    fn from(damage: SpellDamage) -> String {
+        damage.to_string()
+    }
+}
+
+impl fmt::Display for SpellDamage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.level.letter())?;
+        if self.kind == DamageType::Stun {
+            write!(f, " Stun")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Spell<T: SpellTargetNumber> {
-    pub name: &'static str,
-    pub drain_level: DamageLevel,
-    pub drain_modifier: i32,
-    pub target: T
+    pub name: SpellName,
+    pub category: Category,
+    #[serde(rename = "type")]
+    pub kind: SpellType,
+    pub duration: Duration,
+    pub range: SpellRange,
+    pub drain: Drain,
+    pub target: T,
+    /// Combat spells hurt their target, staged by the net successes of the
+    /// casting over the target's resistance.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub damage: Option<SpellDamage>,
 }
 
 impl<S> Spell<S> where S: SpellTargetNumber {
     pub fn to_tn<T:HasAttrs>(&self, target: &T) -> TargetNumber {
         self.target.to_tn(target)
     }
+
+    fn describe(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {:?}, {:?}, {:?}, {:?}, drain {}", self.name, self.category,
+               self.kind, self.duration, self.range, self.drain)
+    }
+}
+
+/// A spell as kept in a grimoire: its target number is either fixed, or
+/// comes from one of the target's attributes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KnownSpell {
+    Fixed(Spell<TargetNumber>),
+    Resisted(Spell<Attribute>),
+}
+
+impl KnownSpell {
+    pub fn name(&self) -> &str {
+        match *self {
+            KnownSpell::Fixed(ref s) => &s.name,
+            KnownSpell::Resisted(ref s) => &s.name,
+        }
+    }
+
+    pub fn category(&self) -> Category {
+        match *self {
+            KnownSpell::Fixed(ref s) => s.category,
+            KnownSpell::Resisted(ref s) => s.category,
+        }
+    }
+}
+
+impl fmt::Display for KnownSpell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KnownSpell::Fixed(ref s) => {
+                s.describe(f)?;
+                write!(f, ", TN {}", s.target)
+            },
+            KnownSpell::Resisted(ref s) => {
+                s.describe(f)?;
+                write!(f, ", resisted by {:?}", s.target)
+            },
+        }
+    }
 }
 
-#[derive(Debug)]
+/// A spell kept up after it was cast, on `target` or else on the caster.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SustainedSpell {
+    pub spell: SpellName,
+    pub force: ForceLevel,
+    pub target: Option<String>,
+}
+
+impl fmt::Display for SustainedSpell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.spell, self.force)?;
+        if let Some(ref target) = self.target {
+            write!(f, " on {}", target)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
 pub struct SpellResult {
     pub success: bool,
     pub successes: i32,
     pub drain_result: Option<DamageLevel>,
+    /// The target's resistance test against a combat spell.
+    pub resistance: Option<RollResult>,
+    /// What the target took after staging, if anything.
+    pub damage: Option<(DamageLevel, DamageType)>,
 }
 
 impl SpellResult {
@@ -58,6 +318,39 @@ impl SpellResult {
             success: roll.success,
             successes: roll.successes,
             drain_result: drain_damage,
+            resistance: None,
+            damage: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::{DamageLevel, DamageType};
+    use magic::{Drain, SpellDamage};
+
+    #[test]
+    fn test_drain_codes() {
+        assert_eq!("(F/2)+1 S".parse(), Ok(Drain::new(1, DamageLevel::Serious)));
+        assert_eq!("(F/2) M".parse(), Ok(Drain::new(0, DamageLevel::Moderate)));
+        assert_eq!("(f/2) - 1 d".parse(), Ok(Drain::new(-1, DamageLevel::Deadly)));
+        assert_eq!("(F/2)+2 SM".parse::<Drain>(), Err(()));
+        assert_eq!("F/2+1 S".parse::<Drain>(), Err(()));
+        assert_eq!("(F/2)+x L".parse::<Drain>(), Err(()));
+        for code in &["(F/2)+1 S", "(F/2) M", "(F/2)-1 D", "(F/2)+2 V"] {
+            assert_eq!(code.parse::<Drain>().unwrap().to_string(), *code);
+        }
+        assert_eq!(Drain::new(2, DamageLevel::Light).target_number(5), 4);
+    }
+
+    #[test]
+    fn test_damage_codes() {
+        assert_eq!("M".parse(), Ok(SpellDamage::new(DamageLevel::Moderate, DamageType::Physical)));
+        assert_eq!("s stun".parse(), Ok(SpellDamage::new(DamageLevel::Serious, DamageType::Stun)));
+        assert_eq!("M fire".parse::<SpellDamage>(), Err(()));
+        assert_eq!("".parse::<SpellDamage>(), Err(()));
+        for code in &["L", "D Stun"] {
+            assert_eq!(code.parse::<SpellDamage>().unwrap().to_string(), *code);
         }
     }
 }
@@ -1,17 +1,19 @@
 extern crate getopts;
+extern crate shadowfun;
 
 use getopts::Options;
 use std::env;
-mod dice;
-mod character;
-mod common;
-mod magic;
+use std::io;
+use std::path::{Path, PathBuf};
+mod console;
 
-use common::{Attribute, DamageLevel};
-use common::DamageType::{Physical, Stun};
-use dice::{DefaultRoller, Roller};
-use character::{Character, Race};
-use magic::{Spell};
+use shadowfun::campaign::Campaign;
+use shadowfun::common::Attribute;
+use shadowfun::common::DamageType::{Physical, Stun};
+use shadowfun::dice::{DefaultRoller, Roller, SeededRoller};
+use shadowfun::character::{Character, Race};
+use shadowfun::grimoire::Grimoire;
+use shadowfun::notation::Expr;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -21,75 +23,132 @@ fn main() {
     opts.optflag("h", "help", "Show this usage message.");
     opts.optflag("g", "go", "Enter the shadowfun console");
     opts.optflag("d", "demo", "Run the demo");
+    opts.optopt("s", "seed", "Seed the dice so a session can be replayed", "SEED");
+    opts.optopt("c", "campaign", "Load a campaign into the console, or start one there",
+                "PATH");
 
     println!("/ / / S H A D O W  F U N \\ \\ \\");
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => {m},
-        Err(e) => { panic!(e.to_string()) }
+        Err(e) => {
+            println!("{}", e);
+            usage(&program, opts);
+            return;
+        }
+    };
+    let seed = match matches.opt_str("s") {
+        Some(s) => match s.parse::<u64>() {
+            Ok(seed) => Some(seed),
+            Err(_) => {
+                println!("'{}' is not a valid seed", s);
+                usage(&program, opts);
+                return;
+            }
+        },
+        None => None,
     };
-    if matches.opt_present("g") {
-        go();
+    if matches.free.first().map(|s| s.as_str()) == Some("roll") {
+        let expr = matches.free[1..].join(" ");
+        match seed {
+            Some(seed) => roll(&SeededRoller::with_seed(false, seed), &expr),
+            None => roll(&DefaultRoller::new(false), &expr),
+        }
+    } else if matches.opt_present("g") || matches.opt_present("c") {
+        // Console sessions are always seeded so any of them can be replayed.
+        let roller = match seed {
+            Some(seed) => SeededRoller::with_seed(false, seed),
+            None => SeededRoller::new(false),
+        };
+        println!("dice seed: {} (pass --seed to replay)", roller.seed());
+        go(&roller, matches.opt_str("c"));
     } else if matches.opt_present("d") {
-        demo();
+        match seed {
+            Some(seed) => demo(&SeededRoller::with_seed(true, seed)),
+            None => demo(&DefaultRoller::new(true)),
+        }
     } else {
         usage(&program, opts);
     }
 }
 
-fn go() {
-    println!("runnin'");
-    // TODO prob put this in a module
-    // TODO loop
-    // TODO basic PoC commands
-    // TODO think about command processing
+fn go<R: Roller>(roller: &R, path: Option<String>) {
+    let mut session = match path {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            match open_campaign(&path) {
+                Ok(campaign) => console::Session::with_campaign(roller, campaign, Some(path)),
+                Err(e) => {
+                    println!("could not load {}: {}", path.display(), e);
+                    return;
+                }
+            }
+        },
+        None => console::Session::new(roller),
+    };
+    println!("type 'help' for a list of commands");
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    if let Err(e) = console::run(&mut session, stdin.lock(), &mut stdout.lock()) {
+        println!("console error: {}", e);
+    }
 }
 
-fn usage(program: &str, opts: Options) {
-    println!("{}", opts.usage(&format!("Usage: {} [options]", program)));
+/// Loads the campaign at `path`, or starts a new one if there's nothing there
+/// yet; it gets written on the first `save`.
+fn open_campaign(path: &Path) -> Result<Campaign, shadowfun::campaign::CampaignError> {
+    if path.exists() {
+        let campaign = Campaign::load(path)?;
+        println!("loaded {} ({} in the party, {} npcs)",
+                 path.display(), campaign.party.len(), campaign.npcs.len());
+        Ok(campaign)
+    } else {
+        println!("starting a new campaign at {}", path.display());
+        Ok(Campaign::new())
+    }
 }
 
-fn demo() {
-    let roller = DefaultRoller::new(true);
+fn roll<R: Roller>(roller: &R, expr: &str) {
+    match expr.parse::<Expr>() {
+        Ok(expr) => println!("{}", expr.roll(roller)),
+        Err(e) => println!("bad roll: {}", e),
+    }
+}
+
+fn usage(program: &str, opts: Options) {
+    let brief = format!("Usage: {0} [options]\n       {0} [--seed SEED] roll \"<dice>\"",
+                        program);
+    println!("{}", opts.usage(&brief));
+}
 
+fn demo<R: Roller>(roller: &R) {
     println!("d6: {}", roller.d6());
     println!("nd6: {}", roller.nd6(4));
-    println!("roll: {:?}", roller.roll(12,20));
+    println!("roll: {}", roller.roll(12,20));
 
-    let mut froz = Character::new("froz boz", Race::Troll, &roller);
+    let mut froz = Character::new("froz boz", Race::Troll);
     println!("char: {:?}", froz);
     println!("char's reaction: {}", froz.reaction());
-    println!("char roll: {:?}", froz.roll(4, 4));
+    println!("char roll: {}", froz.roll(roller, 4, 4));
     println!("char after stun: {:?}", froz.injure(Stun, 1));
-    println!("char roll: {:?}", froz.roll(4, 4));
+    println!("char roll: {}", froz.roll(roller, 4, 4));
     println!("char after phys: {:?}", froz.injure(Physical, 4));
-    println!("char roll: {:?}", froz.roll(4, 4));
+    println!("char roll: {}", froz.roll(roller, 4, 4));
     println!("char dying: {:?}", froz.injure(Stun, 10));
-    println!("char roll: {:?}", froz.roll(4, 4));
+    println!("char roll: {}", froz.roll(roller, 4, 4));
     println!("char killed: {:?}", froz.injure(Physical, 7));
-    println!("char roll: {:?}", froz.roll(4, 4));
+    println!("char roll: {}", froz.roll(roller, 4, 4));
 
     println!("\n~~ * ~ * ~ * skill stuff * ~ * ~ * ~~");
-    let mut jill = Character::new("jill", Race::Ork, &roller);
+    let mut jill = Character::new("jill", Race::Ork);
     jill.learn_skill("edged weapons");
     jill.improve_skill_by("edged weapons", 5);
-    let roll = jill.skill_test("edged weapons", 4);
-    println!("Jill makes a test with edged weapons: {:?}", roll);
+    let roll = jill.skill_test(roller, "edged weapons", &[], 4);
+    println!("Jill makes a test with edged weapons: {}", roll);
 
     println!("\n~~ * ~ * ~ * spell stuff * ~ * ~ * ~~");
-    let oxygenate = Spell {
-        name: "oxygenate",
-        drain_level: DamageLevel::Light,
-        drain_modifier: 2,
-        target: 4,
-    };
-
-    let confuse = Spell {
-        name: "confuse",
-        drain_level: DamageLevel::Serious,
-        drain_modifier: 0,
-        target: Attribute::Willpower,
-    };
-    jill.willpower = 3;
+    let grimoire = Grimoire::core();
+    jill.set_attr(Attribute::Willpower, 3).expect("orks can have willpower 3");
+    jill.set_magic(6);
     jill.learn_spell("confuse");
     jill.learn_skill("sorcery");
     jill.improve_spell_by("confuse", 5);
@@ -98,13 +157,15 @@ fn demo() {
     jill.improve_spell_by("oxygenate", 2);
 
     println!("jill is casting oxygenate");
-    let oxy_sr = jill.cast(&oxygenate);
+    let oxy_sr = jill.cast_named(roller, &grimoire, "oxygenate")
+        .expect("oxygenate is in the core grimoire");
     println!("\t{:?}", oxy_sr);
 
-    let mut frank = Character::new("frank", Race::Elf, &roller);
-    frank.willpower = 4;
+    let mut frank = Character::new("frank", Race::Elf);
+    frank.set_attr(Attribute::Willpower, 4).expect("elves can have willpower 4");
     println!("jill is casting confuse at frank");
-    let conf_sr = jill.cast_at(&confuse, &frank);
+    let conf_sr = jill.cast_named_at(roller, &grimoire, "confuse", &mut frank)
+        .expect("confuse is in the core grimoire");
     if conf_sr.success {
         println!("jill succeeded at confusing frank");
         if let Some(dl) = conf_sr.drain_result {
@@ -0,0 +1,410 @@
+//! Dice notation, e.g. `4d6`, `3d6+2`, `8d6!`, `6d6 tn5` or
+//! `10d6 tn4 vs 6d6 tn5`.
+use std::fmt;
+use std::str::FromStr;
+
+use common::TargetNumber;
+use dice::{format_dice, Die, RollResult, Roller};
+
+/// Nobody needs more dice than this, and a typo shouldn't hang the console.
+pub const MAX_DICE: i32 = 1000;
+
+/// A handful of d6s: summed if there is no target number, counted for
+/// successes if there is one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiceTerm {
+    pub count: i32,
+    pub exploding: bool,
+    pub modifier: i32,
+    pub target: Option<TargetNumber>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Roll(DiceTerm),
+    Opposed(DiceTerm, DiceTerm),
+}
+
+/// Writes the term back out in notation that parses to the same thing.
+impl fmt::Display for DiceTerm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}d6", self.count)?;
+        if self.exploding {
+            write!(f, "!")?;
+        }
+        if self.modifier > 0 {
+            write!(f, "+{}", self.modifier)?;
+        } else if self.modifier < 0 {
+            write!(f, "-{}", -self.modifier)?;
+        }
+        if let Some(tn) = self.target {
+            write!(f, " tn{}", tn)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expr::Roll(ref term) => write!(f, "{}", term),
+            Expr::Opposed(ref a, ref b) => write!(f, "{} vs {}", a, b),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExprError {
+    Empty,
+    UnexpectedChar(char),
+    UnknownWord(String),
+    BadNumber(String),
+    Expected(&'static str),
+    UnsupportedDie(i32),
+    TooManyDice(i32),
+    ModifierInTest,
+    OpposedWithoutTarget,
+    TrailingInput,
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExprError::Empty =>
+                write!(f, "nothing to roll"),
+            ExprError::UnexpectedChar(c) =>
+                write!(f, "unexpected '{}'", c),
+            ExprError::UnknownWord(ref w) =>
+                write!(f, "unexpected '{}'", w),
+            ExprError::BadNumber(ref n) =>
+                write!(f, "'{}' is not a usable number", n),
+            ExprError::Expected(what) =>
+                write!(f, "expected {}", what),
+            ExprError::UnsupportedDie(sides) =>
+                write!(f, "only d6 are rolled here, not d{}", sides),
+            ExprError::TooManyDice(n) =>
+                write!(f, "{} dice is more than the {} allowed", n, MAX_DICE),
+            ExprError::ModifierInTest =>
+                write!(f, "a test with a target number can't take a +/- modifier"),
+            ExprError::OpposedWithoutTarget =>
+                write!(f, "both sides of an opposed test need a target number"),
+            ExprError::TrailingInput =>
+                write!(f, "unexpected input after the roll"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Number(i32),
+    D,
+    Bang,
+    Plus,
+    Minus,
+    Tn,
+    Vs,
+}
+
+fn lex(input: &str) -> Result<Vec<Token>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut digits = String::new();
+            while let Some(&d) = chars.peek() {
+                if !d.is_ascii_digit() {
+                    break;
+                }
+                digits.push(d);
+                chars.next();
+            }
+            match digits.parse() {
+                Ok(n) => tokens.push(Token::Number(n)),
+                Err(_) => return Err(ExprError::BadNumber(digits)),
+            }
+        } else if c.is_alphabetic() {
+            let mut word = String::new();
+            while let Some(&l) = chars.peek() {
+                if !l.is_alphabetic() {
+                    break;
+                }
+                word.push(l);
+                chars.next();
+            }
+            match word.to_lowercase().as_str() {
+                "d" => tokens.push(Token::D),
+                "tn" => tokens.push(Token::Tn),
+                "vs" => tokens.push(Token::Vs),
+                _ => return Err(ExprError::UnknownWord(word)),
+            }
+        } else {
+            tokens.push(match c {
+                '!' => Token::Bang,
+                '+' => Token::Plus,
+                '-' => Token::Minus,
+                _ => return Err(ExprError::UnexpectedChar(c)),
+            });
+            chars.next();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn number(&mut self, what: &'static str) -> Result<i32, ExprError> {
+        match self.peek() {
+            Some(&Token::Number(n)) => {
+                self.pos += 1;
+                Ok(n)
+            },
+            _ => Err(ExprError::Expected(what)),
+        }
+    }
+
+    fn term(&mut self) -> Result<DiceTerm, ExprError> {
+        let count = match self.peek() {
+            Some(&Token::Number(n)) => {
+                self.pos += 1;
+                n
+            },
+            _ => 1,
+        };
+        if !self.eat(&Token::D) {
+            return Err(ExprError::Expected("a roll like 4d6"));
+        }
+        let sides = self.number("the number of sides")?;
+        if sides != 6 {
+            return Err(ExprError::UnsupportedDie(sides));
+        }
+        if count < 1 {
+            return Err(ExprError::Expected("at least one die"));
+        }
+        if count > MAX_DICE {
+            return Err(ExprError::TooManyDice(count));
+        }
+        let exploding = self.eat(&Token::Bang);
+
+        let modifier = if self.eat(&Token::Plus) {
+            self.number("a modifier")?
+        } else if self.eat(&Token::Minus) {
+            -self.number("a modifier")?
+        } else {
+            0
+        };
+
+        let target = if self.eat(&Token::Tn) {
+            if modifier != 0 {
+                return Err(ExprError::ModifierInTest);
+            }
+            Some(self.number("a target number")?)
+        } else {
+            None
+        };
+
+        Ok(DiceTerm { count, exploding, modifier, target })
+    }
+
+    fn expr(&mut self) -> Result<Expr, ExprError> {
+        if self.tokens.is_empty() {
+            return Err(ExprError::Empty);
+        }
+        let first = self.term()?;
+        let expr = if self.eat(&Token::Vs) {
+            let second = self.term()?;
+            if first.target.is_none() || second.target.is_none() {
+                return Err(ExprError::OpposedWithoutTarget);
+            }
+            Expr::Opposed(first, second)
+        } else {
+            Expr::Roll(first)
+        };
+        if self.peek().is_some() {
+            return Err(ExprError::TrailingInput);
+        }
+        Ok(expr)
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Expr, ExprError> {
+        Parser { tokens: lex(s)?, pos: 0 }.expr()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ExprResult {
+    Total { dice: Vec<Die>, modifier: i32, total: i32 },
+    Test(RollResult),
+    Opposed { attacker: RollResult, defender: RollResult, net: i32 },
+}
+
+impl fmt::Display for ExprResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExprResult::Total { ref dice, modifier, total } => {
+                write!(f, "{}", format_dice(dice))?;
+                if modifier > 0 {
+                    write!(f, " + {}", modifier)?;
+                } else if modifier < 0 {
+                    write!(f, " - {}", -modifier)?;
+                }
+                write!(f, " = {}", total)
+            },
+            ExprResult::Test(ref test) => write!(f, "{}", test),
+            ExprResult::Opposed { ref attacker, ref defender, net } =>
+                write!(f, "{}\n{}\nnet {}", attacker, defender, net),
+        }
+    }
+}
+
+impl DiceTerm {
+    fn dice<R: Roller>(&self, roller: &R) -> Vec<Die> {
+        // Success tests always explode under the Rule of Six.
+        let exploding = self.exploding || self.target.is_some();
+        (0..self.count).map(|_| roller.roll_die(exploding)).collect()
+    }
+
+    fn test<R: Roller>(&self, roller: &R, target: TargetNumber) -> RollResult {
+        RollResult::from_dice(self.dice(roller), target)
+    }
+}
+
+impl Expr {
+    pub fn roll<R: Roller>(&self, roller: &R) -> ExprResult {
+        match *self {
+            Expr::Roll(ref term) => match term.target {
+                Some(target) => ExprResult::Test(term.test(roller, target)),
+                None => {
+                    let dice = term.dice(roller);
+                    let total = dice.iter().map(Die::total).sum::<i32>() + term.modifier;
+                    ExprResult::Total { dice, modifier: term.modifier, total }
+                },
+            },
+            Expr::Opposed(ref a, ref b) => {
+                // The parser won't build an opposed test without both targets.
+                let attacker = a.test(roller, a.target.unwrap_or(0));
+                let defender = b.test(roller, b.target.unwrap_or(0));
+                let net = attacker.successes - defender.successes;
+                ExprResult::Opposed { attacker, defender, net }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dice::{Die, ScriptedRoller};
+    use notation::{DiceTerm, Expr, ExprError, ExprResult};
+
+    fn term(count: i32, exploding: bool, modifier: i32, target: Option<i32>)
+            -> DiceTerm {
+        DiceTerm { count, exploding, modifier, target }
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("4d6".parse(), Ok(Expr::Roll(term(4, false, 0, None))));
+        assert_eq!("d6".parse(), Ok(Expr::Roll(term(1, false, 0, None))));
+        assert_eq!("3d6+2".parse(), Ok(Expr::Roll(term(3, false, 2, None))));
+        assert_eq!("3D6 - 2".parse(), Ok(Expr::Roll(term(3, false, -2, None))));
+        assert_eq!("8d6!".parse(), Ok(Expr::Roll(term(8, true, 0, None))));
+        assert_eq!("6d6 tn5".parse(), Ok(Expr::Roll(term(6, false, 0, Some(5)))));
+        assert_eq!("10d6 tn4 vs 6d6 TN 5".parse(),
+                   Ok(Expr::Opposed(term(10, false, 0, Some(4)),
+                                    term(6, false, 0, Some(5)))));
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        for text in &["4d6", "3d6+2", "3d6-2", "8d6!", "6d6 tn5", "10d6 tn4 vs 6d6 tn5"] {
+            let expr: Expr = text.parse().unwrap();
+            assert_eq!(expr.to_string(), *text);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!("".parse::<Expr>(), Err(ExprError::Empty));
+        assert_eq!("4d20".parse::<Expr>(), Err(ExprError::UnsupportedDie(20)));
+        assert_eq!("4d6 tn".parse::<Expr>(), Err(ExprError::Expected("a target number")));
+        assert_eq!("4x6".parse::<Expr>(), Err(ExprError::UnknownWord("x".to_owned())));
+        assert_eq!("4d6 * 2".parse::<Expr>(), Err(ExprError::UnexpectedChar('*')));
+        assert_eq!("4d6 4d6".parse::<Expr>(), Err(ExprError::TrailingInput));
+        assert_eq!("4d6+1 tn4".parse::<Expr>(), Err(ExprError::ModifierInTest));
+        assert_eq!("4d6 vs 4d6 tn4".parse::<Expr>(), Err(ExprError::OpposedWithoutTarget));
+        assert_eq!("0d6".parse::<Expr>(), Err(ExprError::Expected("at least one die")));
+        assert_eq!("5000d6".parse::<Expr>(), Err(ExprError::TooManyDice(5000)));
+        assert_eq!("99999999999d6".parse::<Expr>(),
+                   Err(ExprError::BadNumber("99999999999".to_owned())));
+    }
+
+    #[test]
+    fn test_total() {
+        let roller = ScriptedRoller::with(&[3, 6, 5]);
+        let result = "3d6+2".parse::<Expr>().unwrap().roll(&roller);
+        assert_eq!(result, ExprResult::Total {
+            dice: vec![Die { faces: vec![3] }, Die { faces: vec![6] },
+                       Die { faces: vec![5] }],
+            modifier: 2,
+            total: 16,
+        });
+        assert_eq!(result.to_string(), "[3, 6, 5] + 2 = 16");
+    }
+
+    #[test]
+    fn test_exploding_total() {
+        let roller = ScriptedRoller::with(&[6, 6, 2, 4]);
+        let result = "2d6!".parse::<Expr>().unwrap().roll(&roller);
+        assert_eq!(result.to_string(), "[6+6+2, 4] = 18");
+    }
+
+    #[test]
+    fn test_success_test() {
+        let roller = ScriptedRoller::with(&[5, 6, 6, 2, 1, 3]);
+        let result = "4d6 tn4".parse::<Expr>().unwrap().roll(&roller);
+        assert_eq!(result.to_string(), "[5, 6+6+2, 1, 3] vs TN 4 \u{2192} 2 successes");
+
+        let roller = ScriptedRoller::with(&[1, 1]);
+        let result = "2d6 tn4".parse::<Expr>().unwrap().roll(&roller);
+        match result {
+            ExprResult::Test(ref test) => assert!(test.catastrophic_fail),
+            _ => panic!("expected a test, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_opposed() {
+        let roller = ScriptedRoller::with(&[5, 5, 5, 1, 6, 3]);
+        let result = "3d6 tn4 vs 2d6 tn5".parse::<Expr>().unwrap().roll(&roller);
+        match result {
+            ExprResult::Opposed { ref attacker, ref defender, net } => {
+                assert_eq!(attacker.successes, 3);
+                assert_eq!(defender.successes, 1);
+                assert_eq!(net, 2);
+            },
+            _ => panic!("expected an opposed test, got {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! Dice pools: extra dice a character can throw into tests over a combat
+//! turn. The Combat Pool feeds attacks, dodging and damage resistance, the
+//! Magic Pool feeds spellcasting, drain and spell defense, and the Karma Pool
+//! pays for rerolls. Whatever's spent comes back at the start of the next turn.
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pool {
+    Combat,
+    Magic,
+    Karma,
+}
+
+/// The tests pool dice can be committed to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PoolTest {
+    Attack,
+    Dodge,
+    DamageResistance,
+    Spellcasting,
+    Drain,
+    SpellDefense,
+}
+
+impl FromStr for PoolTest {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<PoolTest, ()> {
+        match s.to_lowercase().replace(' ', "").as_str() {
+            "attack" => Ok(PoolTest::Attack),
+            "dodge" => Ok(PoolTest::Dodge),
+            "damageresistance" | "resistance" => Ok(PoolTest::DamageResistance),
+            "spellcasting" | "casting" => Ok(PoolTest::Spellcasting),
+            "drain" => Ok(PoolTest::Drain),
+            "spelldefense" | "defense" => Ok(PoolTest::SpellDefense),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for PoolTest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            PoolTest::Attack => "attack",
+            PoolTest::Dodge => "dodge",
+            PoolTest::DamageResistance => "damage resistance",
+            PoolTest::Spellcasting => "spellcasting",
+            PoolTest::Drain => "drain",
+            PoolTest::SpellDefense => "spell defense",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl PoolTest {
+    /// The pool that pays for this test.
+    pub fn pool(self) -> Pool {
+        match self {
+            PoolTest::Attack | PoolTest::Dodge | PoolTest::DamageResistance => Pool::Combat,
+            PoolTest::Spellcasting | PoolTest::Drain | PoolTest::SpellDefense => Pool::Magic,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PoolError {
+    /// Carries how many dice the pool has left.
+    NotEnough(Pool, i32),
+    NoDice(i32),
+    /// Karma can't reroll a roll without failures...
+    NothingToReroll,
+    /// ...avert a disaster that didn't happen...
+    NotCatastrophic,
+    /// ...or buy successes for a roll that didn't get any.
+    NoSuccesses,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PoolError::NotEnough(pool, left) =>
+                write!(f, "only {} dice left in the {:?} Pool", left, pool),
+            PoolError::NoDice(dice) =>
+                write!(f, "can't commit {} dice", dice),
+            PoolError::NothingToReroll =>
+                write!(f, "every die already succeeded"),
+            PoolError::NotCatastrophic =>
+                write!(f, "that wasn't a catastrophic failure"),
+            PoolError::NoSuccesses =>
+                write!(f, "successes can only be bought for a roll with at least one"),
+        }
+    }
+}
+
+/// What's been spent from each pool this turn, and what's been set aside for
+/// tests still to come. Nothing spent means every pool is full.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PoolState {
+    combat: i32,
+    magic: i32,
+    karma: i32,
+    committed: Vec<(PoolTest, i32)>,
+}
+
+impl PoolState {
+    pub fn spent(&self, pool: Pool) -> i32 {
+        match pool {
+            Pool::Combat => self.combat,
+            Pool::Magic => self.magic,
+            Pool::Karma => self.karma,
+        }
+    }
+
+    /// Takes `dice` out of `pool`, which has `left` to give.
+    pub fn spend(&mut self, pool: Pool, dice: i32, left: i32) -> Result<(), PoolError> {
+        if dice < 1 {
+            return Err(PoolError::NoDice(dice));
+        }
+        if dice > left {
+            return Err(PoolError::NotEnough(pool, left));
+        }
+        match pool {
+            Pool::Combat => self.combat += dice,
+            Pool::Magic => self.magic += dice,
+            Pool::Karma => self.karma += dice,
+        }
+        Ok(())
+    }
+
+    /// Spends `dice` from the pool behind `test` and holds them for it.
+    pub fn commit(&mut self, test: PoolTest, dice: i32, left: i32) -> Result<(), PoolError> {
+        self.spend(test.pool(), dice, left)?;
+        self.committed.push((test, dice));
+        Ok(())
+    }
+
+    pub fn committed(&self, test: PoolTest) -> i32 {
+        self.committed.iter().filter(|&&(t, _)| t == test).map(|&(_, d)| d).sum()
+    }
+
+    /// Hands over the dice committed to `test`; they're used up.
+    pub fn take(&mut self, test: PoolTest) -> i32 {
+        let dice = self.committed(test);
+        self.committed.retain(|&(t, _)| t != test);
+        dice
+    }
+
+    /// Fills every pool back up for a new turn. Dice committed to a test
+    /// that never came are lost.
+    pub fn refresh(&mut self) {
+        *self = PoolState::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pools::{Pool, PoolError, PoolState, PoolTest};
+
+    #[test]
+    fn test_committing() {
+        let mut state = PoolState::default();
+        state.commit(PoolTest::Dodge, 2, 5).unwrap();
+        state.commit(PoolTest::Dodge, 1, 3).unwrap();
+        state.commit(PoolTest::Drain, 2, 2).unwrap();
+        assert_eq!(state.spent(Pool::Combat), 3);
+        assert_eq!(state.spent(Pool::Magic), 2);
+        assert_eq!(state.commit(PoolTest::Attack, 3, 2), Err(PoolError::NotEnough(Pool::Combat, 2)));
+        assert_eq!(state.commit(PoolTest::Attack, 0, 2), Err(PoolError::NoDice(0)));
+
+        assert_eq!(state.take(PoolTest::Dodge), 3);
+        assert_eq!(state.take(PoolTest::Dodge), 0);
+        assert_eq!(state.spent(Pool::Combat), 3);
+        state.refresh();
+        assert_eq!(state.spent(Pool::Combat), 0);
+        assert_eq!(state.committed(PoolTest::Drain), 0);
+        assert_eq!("damage resistance".parse(), Ok(PoolTest::DamageResistance));
+        assert_eq!("Defense".parse(), Ok(PoolTest::SpellDefense));
+    }
+}
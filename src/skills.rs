@@ -0,0 +1,215 @@
+//! Active skills, the attributes they hang off and the skill web that ties
+//! related skills together, for defaulting when a character lacks one.
+use std::fmt;
+
+use common::Attribute;
+
+/// Defaulting to a related skill adds this to the target number...
+pub const SKILL_DEFAULT_PENALTY: i32 = 2;
+/// ...and defaulting to the linked attribute adds this.
+pub const ATTRIBUTE_DEFAULT_PENALTY: i32 = 4;
+
+/// Skills in the same group are related on the skill web.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkillGroup {
+    Firearms,
+    Melee,
+    Thrown,
+    Heavy,
+    Athletic,
+    Social,
+    Technical,
+    /// Magical skills never default.
+    Magical,
+}
+
+/// Active skills by name, with their linked attribute and group.
+const ACTIVE_SKILLS: &[(&str, Attribute, SkillGroup)] = &[
+    ("assault rifles", Attribute::Quickness, SkillGroup::Firearms),
+    ("athletics", Attribute::Body, SkillGroup::Athletic),
+    ("biotech", Attribute::Intelligence, SkillGroup::Technical),
+    ("clubs", Attribute::Strength, SkillGroup::Melee),
+    ("computers", Attribute::Intelligence, SkillGroup::Technical),
+    ("conjuring", Attribute::Willpower, SkillGroup::Magical),
+    ("demolitions", Attribute::Intelligence, SkillGroup::Technical),
+    ("diving", Attribute::Body, SkillGroup::Athletic),
+    ("edged weapons", Attribute::Strength, SkillGroup::Melee),
+    ("electronics", Attribute::Intelligence, SkillGroup::Technical),
+    ("etiquette", Attribute::Charisma, SkillGroup::Social),
+    ("gunnery", Attribute::Intelligence, SkillGroup::Heavy),
+    ("heavy weapons", Attribute::Strength, SkillGroup::Heavy),
+    ("instruction", Attribute::Charisma, SkillGroup::Social),
+    ("interrogation", Attribute::Charisma, SkillGroup::Social),
+    ("intimidation", Attribute::Charisma, SkillGroup::Social),
+    ("launch weapons", Attribute::Intelligence, SkillGroup::Heavy),
+    ("leadership", Attribute::Charisma, SkillGroup::Social),
+    ("negotiation", Attribute::Charisma, SkillGroup::Social),
+    ("pistols", Attribute::Quickness, SkillGroup::Firearms),
+    ("polearms", Attribute::Strength, SkillGroup::Melee),
+    ("projectile weapons", Attribute::Strength, SkillGroup::Thrown),
+    ("rifles", Attribute::Quickness, SkillGroup::Firearms),
+    ("shotguns", Attribute::Quickness, SkillGroup::Firearms),
+    ("sorcery", Attribute::Willpower, SkillGroup::Magical),
+    ("stealth", Attribute::Quickness, SkillGroup::Athletic),
+    ("submachine guns", Attribute::Quickness, SkillGroup::Firearms),
+    ("throwing weapons", Attribute::Strength, SkillGroup::Thrown),
+    ("unarmed combat", Attribute::Strength, SkillGroup::Melee),
+    ("whips", Attribute::Quickness, SkillGroup::Melee),
+];
+
+fn lookup(skill: &str) -> Option<&'static (&'static str, Attribute, SkillGroup)> {
+    ACTIVE_SKILLS.iter().find(|&&(name, _, _)| name.eq_ignore_ascii_case(skill))
+}
+
+/// The attribute an active skill is linked to. Skills this table doesn't
+/// know are taken to be technical ones, linked to Intelligence.
+pub fn linked_attribute(skill: &str) -> Attribute {
+    lookup(skill).map_or(Attribute::Intelligence, |&(_, attr, _)| attr)
+}
+
+pub fn group(skill: &str) -> Option<SkillGroup> {
+    lookup(skill).map(|&(_, _, group)| group)
+}
+
+/// The skills next to `skill` on the web.
+pub fn related(skill: &str) -> Vec<&'static str> {
+    match group(skill) {
+        None | Some(SkillGroup::Magical) => Vec::new(),
+        Some(g) => ACTIVE_SKILLS.iter()
+            .filter(|&&(name, _, other)| other == g && !name.eq_ignore_ascii_case(skill))
+            .map(|&(name, _, _)| name)
+            .collect(),
+    }
+}
+
+pub fn can_default(skill: &str) -> bool {
+    group(skill) != Some(SkillGroup::Magical)
+}
+
+/// What a test fell back on for want of the skill itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Defaulting {
+    Skill(String),
+    Attribute(Attribute),
+}
+
+impl Defaulting {
+    /// What defaulting this way adds to the target number.
+    pub fn penalty(&self) -> i32 {
+        match *self {
+            Defaulting::Skill(_) => SKILL_DEFAULT_PENALTY,
+            Defaulting::Attribute(_) => ATTRIBUTE_DEFAULT_PENALTY,
+        }
+    }
+}
+
+impl fmt::Display for Defaulting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Defaulting::Skill(ref s) => write!(f, "{}", s),
+            Defaulting::Attribute(a) => write!(f, "{:?}", a),
+        }
+    }
+}
+
+/// A narrower focus within a skill, such as Firearms, then Pistols, then
+/// Ares Predator. Taking one trades a point of the general skill for a
+/// concentration a point above the skill and a specialization a point above
+/// that.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Specialty {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub concentration: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub specialization: Option<String>,
+}
+
+impl Specialty {
+    pub fn general_rating(rating: i32) -> i32 {
+        rating - 1
+    }
+
+    pub fn concentration_rating(&self, rating: i32) -> Option<i32> {
+        self.concentration.as_ref().map(|_| rating + 1)
+    }
+
+    pub fn specialization_rating(&self, rating: i32) -> Option<i32> {
+        let above = if self.concentration.is_some() { 2 } else { 1 };
+        self.specialization.as_ref().map(|_| rating + above)
+    }
+
+    /// Whether `name` is this specialty's concentration or specialization.
+    pub fn covers(&self, name: &str) -> bool {
+        let is = |focus: &Option<String>| focus.as_ref()
+            .is_some_and(|f| f.eq_ignore_ascii_case(name));
+        is(&self.concentration) || is(&self.specialization)
+    }
+
+    /// The rating that applies to a test involving any of `names`, for a
+    /// skill bought at `rating`: the narrowest focus that matches, or the
+    /// general skill.
+    pub fn rating_for(&self, rating: i32, names: &[&str]) -> i32 {
+        let matches = |focus: &Option<String>| focus.as_ref()
+            .is_some_and(|f| names.iter().any(|n| f.eq_ignore_ascii_case(n)));
+        let focus = if matches(&self.specialization) {
+            self.specialization_rating(rating)
+        } else if matches(&self.concentration) {
+            self.concentration_rating(rating)
+        } else {
+            None
+        };
+        focus.unwrap_or_else(|| Specialty::general_rating(rating))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SkillError {
+    NotKnown(String),
+    /// Specializing needs a rating of at least 2 to split; carries the rating.
+    TooLow(String, i32),
+}
+
+impl fmt::Display for SkillError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SkillError::NotKnown(ref s) =>
+                write!(f, "doesn't know {}", s),
+            SkillError::TooLow(ref s, rating) =>
+                write!(f, "{} {} is too low to specialize", s, rating),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::Attribute;
+    use skills::{can_default, linked_attribute, related, Specialty};
+
+    #[test]
+    fn test_skill_web() {
+        assert_eq!(linked_attribute("Pistols"), Attribute::Quickness);
+        assert_eq!(linked_attribute("knitting"), Attribute::Intelligence);
+        let firearms = related("pistols");
+        assert!(firearms.contains(&"rifles"));
+        assert!(!firearms.contains(&"pistols"));
+        assert!(related("sorcery").is_empty());
+        assert!(related("knitting").is_empty());
+        assert!(!can_default("sorcery"));
+        assert!(can_default("knitting"));
+    }
+
+    #[test]
+    fn test_specialty_ratings() {
+        let predator = Specialty { concentration: Some("pistols".to_owned()),
+                                   specialization: Some("ares predator".to_owned()) };
+        assert_eq!(predator.rating_for(5, &["firearms"]), 4);
+        assert_eq!(predator.rating_for(5, &["firearms", "Pistols"]), 6);
+        assert_eq!(predator.rating_for(5, &["pistols", "ares predator"]), 7);
+        assert!(predator.covers("Ares Predator"));
+
+        let knives = Specialty { concentration: None, specialization: Some("knives".to_owned()) };
+        assert_eq!(knives.rating_for(4, &["edged weapons", "knives"]), 5);
+        assert_eq!(knives.specialization_rating(4), Some(5));
+        assert_eq!(knives.concentration_rating(4), None);
+    }
+}
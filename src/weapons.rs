@@ -0,0 +1,372 @@
+//! Weapons, their damage codes, and resolving a shot from one character at
+//! another.
+use std::fmt;
+use std::str::FromStr;
+
+use armor::ArmorRating;
+use character::Character;
+use common::{dmg_to_num, DamageLevel, DamageType, TargetNumber};
+use dice::{RollResult, Roller};
+use pools::PoolTest;
+
+/// Power, level and kind of damage, written `9M` or `6S Stun`. Damage is
+/// physical unless it says otherwise.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DamageCode {
+    pub power: i32,
+    pub level: DamageLevel,
+    pub kind: DamageType,
+}
+
+impl FromStr for DamageCode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<DamageCode, ()> {
+        let s = s.trim();
+        let digits = s.find(|c: char| !c.is_ascii_digit()).ok_or(())?;
+        let power = s[..digits].parse().map_err(|_| ())?;
+        let mut rest = s[digits..].chars();
+        let level = rest.next().and_then(DamageLevel::from_letter).ok_or(())?;
+        let kind = match rest.as_str().trim() {
+            "" => DamageType::Physical,
+            kind => kind.parse()?,
+        };
+        Ok(DamageCode { power, level, kind })
+    }
+}
+
+impl fmt::Display for DamageCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.power, self.level.letter())?;
+        if self.kind == DamageType::Stun {
+            write!(f, " Stun")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangeBand {
+    Short,
+    Medium,
+    Long,
+    Extreme,
+}
+
+impl RangeBand {
+    pub fn target_number(self) -> TargetNumber {
+        match self {
+            RangeBand::Short => 4,
+            RangeBand::Medium => 5,
+            RangeBand::Long => 6,
+            RangeBand::Extreme => 9,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Weapon {
+    pub name: String,
+    pub skill: String,
+    pub damage: DamageCode,
+    /// The furthest each of short, medium, long and extreme range reach, in
+    /// meters.
+    pub ranges: [i32; 4],
+    /// The armor rating that stands up to it.
+    pub against: ArmorRating,
+}
+
+impl Weapon {
+    pub fn new(name: &str, skill: &str, damage: &str, ranges: [i32; 4],
+               against: ArmorRating) -> Weapon {
+        Weapon {
+            name: name.to_owned(),
+            skill: skill.to_owned(),
+            damage: damage.parse().expect("weapon damage codes are written by hand"),
+            ranges,
+            against,
+        }
+    }
+
+    /// Which band a target `distance` meters away falls in, if it's in range
+    /// at all.
+    pub fn range_band(&self, distance: i32) -> Option<RangeBand> {
+        let bands = [RangeBand::Short, RangeBand::Medium, RangeBand::Long, RangeBand::Extreme];
+        self.ranges.iter().zip(bands.iter())
+            .find(|&(&reach, _)| distance <= reach)
+            .map(|(_, &band)| band)
+    }
+}
+
+/// The guns everybody knows about.
+pub fn armory() -> Vec<Weapon> {
+    use armor::ArmorRating::{Ballistic, Impact};
+    vec![
+        Weapon::new("light pistol", "pistols", "6L", [5, 15, 30, 50], Ballistic),
+        Weapon::new("heavy pistol", "pistols", "9M", [5, 20, 40, 60], Ballistic),
+        Weapon::new("taser", "pistols", "10S Stun", [5, 10, 12, 15], Impact),
+        Weapon::new("smg", "submachine guns", "6M", [10, 40, 80, 150], Ballistic),
+        Weapon::new("shotgun", "shotguns", "10S", [10, 20, 50, 100], Ballistic),
+        Weapon::new("assault rifle", "assault rifles", "8M", [50, 150, 350, 550], Ballistic),
+        Weapon::new("sniper rifle", "rifles", "14S", [50, 400, 800, 1200], Ballistic),
+    ]
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttackError {
+    OutOfRange(String, i32),
+}
+
+impl fmt::Display for AttackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AttackError::OutOfRange(ref weapon, distance) =>
+                write!(f, "a {} can't reach {}m", weapon, distance),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct AttackResult {
+    pub attack: RollResult,
+    /// The defender's dodge, if they committed Combat Pool to one.
+    pub dodge: Option<RollResult>,
+    /// The defender's Body test, if they were hit.
+    pub resistance: Option<RollResult>,
+    /// What the defender took after staging, if anything.
+    pub damage: Option<DamageLevel>,
+    pub kind: DamageType,
+}
+
+impl fmt::Display for AttackResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let resistance = match (&self.resistance, &self.dodge) {
+            (Some(r), _) => r,
+            (None, Some(d)) if self.attack.success =>
+                return write!(f, "dodged: {}; dodge: {}", self.attack, d),
+            (None, _) => return write!(f, "miss: {}", self.attack),
+        };
+        write!(f, "hit: {}; ", self.attack)?;
+        if let Some(ref d) = self.dodge {
+            write!(f, "dodge: {}; ", d)?;
+        }
+        write!(f, "resisted: {}; ", resistance)?;
+        match self.damage {
+            Some(level) => write!(f, "{:?} {:?} damage", level, self.kind),
+            None => write!(f, "no damage"),
+        }
+    }
+}
+
+/// Shoots `defender`, `distance` meters away, with `weapon`. The attacker
+/// makes a test with the weapon's skill, or a default for it, plus any
+/// Combat Pool committed to the attack, against the range band's target
+/// number. A defender with Combat Pool committed to dodging rolls it against
+/// the same number, and each of their successes cancels one of the
+/// attacker's. Every two successes left stage the damage up a level; a hit
+/// lets the defender resist it with `Character::resist_damage`.
+pub fn ranged_attack<R: Roller>(roller: &R, attacker: &mut Character, weapon: &Weapon,
+                                distance: i32, defender: &mut Character)
+                                -> Result<AttackResult, AttackError> {
+    let band = weapon.range_band(distance)
+        .ok_or_else(|| AttackError::OutOfRange(weapon.name.clone(), distance))?;
+    let code = weapon.damage;
+    let (dice, defaulted) = attacker.skill_dice(&weapon.skill, &[&weapon.name]);
+    let dice = dice + attacker.take_committed(PoolTest::Attack);
+    let attack = attacker.roll_defaulted(roller, dice, band.target_number(), defaulted);
+    let dodge = match defender.take_committed(PoolTest::Dodge) {
+        0 => None,
+        dice => Some(defender.roll(roller, dice, band.target_number())),
+    };
+    let net = attack.successes - dodge.as_ref().map_or(0, |d| d.successes);
+    if !attack.success || net < 1 {
+        return Ok(AttackResult { attack, dodge, resistance: None, damage: None,
+                                 kind: code.kind });
+    }
+
+    let level = code.level.resolve(code.power).stage(net / 2)
+        .expect("staging up always leaves some damage");
+    let resisted = defender.resist_damage(roller, DamageCode { level, ..code }, weapon.against);
+    if let Some(level) = resisted.damage {
+        defender.injure(code.kind, dmg_to_num(level));
+    }
+    Ok(AttackResult {
+        attack,
+        dodge,
+        resistance: Some(resisted.roll),
+        damage: resisted.damage,
+        kind: code.kind,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use character::{Character, Race};
+    use common::{Attribute, DamageLevel, DamageType};
+    use dice::ScriptedRoller;
+    use armor::{Armor, ArmorRating};
+    use pools::{Pool, PoolTest};
+    use skills;
+    use skills::Defaulting;
+    use weapons::{armory, ranged_attack, AttackError, DamageCode, RangeBand, Weapon};
+
+    fn shooter(pistols: i32) -> Character {
+        let mut c = Character::new("sam", Race::Human);
+        c.learn_skill("pistols");
+        c.improve_skill_by("pistols", pistols - 1);
+        c
+    }
+
+    fn target(body: i32) -> Character {
+        let mut c = Character::new("frank", Race::Human);
+        c.set_attr(Attribute::Body, body).unwrap();
+        c
+    }
+
+    #[test]
+    fn test_damage_codes() {
+        let code: DamageCode = "9M".parse().unwrap();
+        assert_eq!(code, DamageCode { power: 9, level: DamageLevel::Moderate,
+                                      kind: DamageType::Physical });
+        let code: DamageCode = "10s stun".parse().unwrap();
+        assert_eq!(code.level, DamageLevel::Serious);
+        assert_eq!(code.kind, DamageType::Stun);
+        assert_eq!(code.to_string(), "10S Stun");
+        assert_eq!("12D".parse::<DamageCode>().unwrap().to_string(), "12D");
+        assert_eq!("8V".parse::<DamageCode>().unwrap().level, DamageLevel::Variable);
+        assert!("M9".parse::<DamageCode>().is_err());
+        assert!("9X".parse::<DamageCode>().is_err());
+        assert!("9M fire".parse::<DamageCode>().is_err());
+    }
+
+    #[test]
+    fn test_range_bands() {
+        let pistol = Weapon::new("heavy pistol", "pistols", "9M", [5, 20, 40, 60],
+                                 ArmorRating::Ballistic);
+        assert_eq!(pistol.range_band(3), Some(RangeBand::Short));
+        assert_eq!(pistol.range_band(20), Some(RangeBand::Medium));
+        assert_eq!(pistol.range_band(41), Some(RangeBand::Extreme));
+        assert_eq!(pistol.range_band(61), None);
+        assert_eq!(RangeBand::Extreme.target_number(), 9);
+        assert!(armory().iter().any(|w| w.name == "taser"));
+        // Nobody should have to default just to fire a stock weapon.
+        for weapon in armory() {
+            assert!(skills::group(&weapon.skill).is_some(), "no skill {}", weapon.skill);
+        }
+    }
+
+    #[test]
+    fn test_staging() {
+        let pistol = Weapon::new("heavy pistol", "pistols", "9M", [5, 20, 40, 60],
+                                 ArmorRating::Ballistic);
+
+        // Four successes against none: staged up two levels.
+        let roller = ScriptedRoller::with(&[5, 5, 5, 5, 1, 1]);
+        let mut frank = target(2);
+        let result = ranged_attack(&roller, &mut shooter(4), &pistol, 3, &mut frank).unwrap();
+        assert_eq!(result.damage, Some(DamageLevel::Deadly));
+
+        // Three against one: staged up one.
+        let roller = ScriptedRoller::with(&[5, 5, 5, 1, 6, 4, 1]);
+        let mut frank = target(2);
+        let result = ranged_attack(&roller, &mut shooter(4), &pistol, 3, &mut frank).unwrap();
+        assert_eq!(result.damage, Some(DamageLevel::Serious));
+        assert!(frank.to_string().contains("phys 6/10"));
+
+        // One against three: staged down one.
+        let roller = ScriptedRoller::with(&[5, 1, 6, 4, 6, 4, 6, 4]);
+        let mut frank = target(3);
+        let result = ranged_attack(&roller, &mut shooter(2), &pistol, 3, &mut frank).unwrap();
+        assert_eq!(result.damage, Some(DamageLevel::Light));
+
+        // One against five: staged away entirely.
+        let roller = ScriptedRoller::with(&[5, 1, 6, 4, 6, 4, 6, 4, 6, 4, 6, 4]);
+        let mut frank = target(5);
+        let result = ranged_attack(&roller, &mut shooter(2), &pistol, 3, &mut frank).unwrap();
+        assert_eq!(result.damage, None);
+        assert!(result.to_string().ends_with("no damage"));
+    }
+
+    #[test]
+    fn test_armor() {
+        let pistol = armory().into_iter().find(|w| w.name == "heavy pistol").unwrap();
+        let taser = armory().into_iter().find(|w| w.name == "taser").unwrap();
+        assert_eq!(taser.against, ArmorRating::Impact);
+
+        // An armor jacket takes power 9 down to TN 4, so Frank's fives count.
+        let roller = ScriptedRoller::with(&[5, 5, 1, 1, 5, 5]);
+        let mut frank = target(2);
+        frank.wear(Armor::new("armor jacket", 5, 3));
+        let result = ranged_attack(&roller, &mut shooter(4), &pistol, 3, &mut frank).unwrap();
+        assert_eq!(result.resistance.unwrap().target, 4);
+        assert_eq!(result.damage, Some(DamageLevel::Moderate));
+
+        // Tasers have to get through Impact instead.
+        let roller = ScriptedRoller::with(&[5, 5, 1, 1, 5, 5]);
+        let result = ranged_attack(&roller, &mut shooter(4), &taser, 3, &mut frank).unwrap();
+        assert_eq!(result.resistance.unwrap().target, 7);
+        assert_eq!(result.damage, Some(DamageLevel::Deadly));
+        assert_eq!(result.kind, DamageType::Stun);
+    }
+
+    #[test]
+    fn test_pool_dice() {
+        let pistol = Weapon::new("heavy pistol", "pistols", "9M", [5, 20, 40, 60],
+                                 ArmorRating::Ballistic);
+
+        // Two pool dice on the attack, three on the dodge: four successes
+        // less two leave a hit staged up one.
+        let roller = ScriptedRoller::with(&[5, 5, 5, 5, 5, 5, 1, 1, 1]);
+        let mut sam = shooter(2);
+        sam.set_attr(Attribute::Quickness, 5).unwrap();
+        let dodger = || {
+            let mut c = target(2);
+            c.set_attr(Attribute::Quickness, 6).unwrap();
+            c
+        };
+        let mut frank = dodger();
+        sam.commit(PoolTest::Attack, 2).unwrap();
+        frank.commit(PoolTest::Dodge, 3).unwrap();
+        let result = ranged_attack(&roller, &mut sam, &pistol, 3, &mut frank).unwrap();
+        assert_eq!(result.attack.dice.len(), 4);
+        assert_eq!(result.dodge.as_ref().unwrap().successes, 2);
+        assert_eq!(result.damage, Some(DamageLevel::Serious));
+        assert!(result.to_string().contains("; dodge: "));
+
+        // A dodge that matches the attack avoids it altogether.
+        let roller = ScriptedRoller::with(&[5, 5, 5, 5]);
+        let mut frank = dodger();
+        frank.commit(PoolTest::Dodge, 2).unwrap();
+        let result = ranged_attack(&roller, &mut sam, &pistol, 3, &mut frank).unwrap();
+        assert!(result.resistance.is_none());
+        assert!(result.to_string().starts_with("dodged: "));
+        assert_eq!(frank.pool_left(Pool::Combat), 2);
+    }
+
+    #[test]
+    fn test_misses() {
+        let pistol = Weapon::new("heavy pistol", "pistols", "9M", [5, 20, 40, 60],
+                                 ArmorRating::Ballistic);
+        let roller = ScriptedRoller::with(&[4, 4]);
+        let mut frank = target(2);
+        let result = ranged_attack(&roller, &mut shooter(2), &pistol, 30, &mut frank).unwrap();
+        assert!(result.resistance.is_none());
+        assert!(result.to_string().starts_with("miss: "));
+        assert_eq!(ranged_attack(&roller, &mut shooter(2), &pistol, 100, &mut frank),
+                   Err(AttackError::OutOfRange("heavy pistol".to_owned(), 100)));
+
+        // Without pistols, Quickness has to do at +4.
+        let roller = ScriptedRoller::with(&[5]);
+        let mut amy = Character::new("amy", Race::Human);
+        let result = ranged_attack(&roller, &mut amy, &pistol, 3, &mut frank).unwrap();
+        assert_eq!(result.attack.target, 8);
+        assert_eq!(result.attack.defaulted, Some(Defaulting::Attribute(Attribute::Quickness)));
+
+        // A specialization in the weapon itself counts.
+        let roller = ScriptedRoller::with(&[1, 1, 1]);
+        let mut sam = shooter(2);
+        sam.specialize("pistols", None, Some("heavy pistol")).unwrap();
+        let result = ranged_attack(&roller, &mut sam, &pistol, 3, &mut frank).unwrap();
+        assert_eq!(result.attack.dice.len(), 3);
+    }
+}
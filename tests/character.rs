@@ -0,0 +1,122 @@
+extern crate serde_json;
+extern crate shadowfun;
+extern crate toml;
+
+use std::rc::Rc;
+
+use shadowfun::advancement::Advance;
+use shadowfun::armor::Armor;
+use shadowfun::character::{Character, Race};
+use shadowfun::cyberware::{Cyberware, Grade, Implant};
+use shadowfun::common::{Attribute, DamageType, HasAttrs};
+use shadowfun::dice::Roller;
+use shadowfun::events::{Event, EventLog};
+use shadowfun::grimoire::Grimoire;
+
+/// Outside code can bring its own dice.
+struct LoadedDice;
+
+impl Roller for LoadedDice {
+    fn new(_: bool) -> Self { LoadedDice }
+    fn verbose(&self) -> bool { false }
+    fn d6(&self) -> i32 { 5 }
+}
+
+#[test]
+fn test_build_and_test_a_runner() {
+    let dice = LoadedDice;
+    let mut jill = Character::new("jill", Race::Ork);
+    jill.set_attr(Attribute::Quickness, 5).unwrap();
+    jill.set_attr(Attribute::Intelligence, 3).unwrap();
+    assert_eq!(jill.attr(Attribute::Quickness), 5);
+    assert_eq!(jill.reaction(), 4);
+
+    jill.learn_skill("edged weapons");
+    jill.improve_skill_by("edged weapons", 3);
+    assert_eq!(jill.skill("edged weapons"), 4);
+    let result = jill.skill_test(&dice, "edged weapons", &[], 4);
+    assert_eq!(result.successes, 4);
+    assert_eq!(result.dice.len(), 4);
+}
+
+#[test]
+fn test_cast_at_another_character() {
+    let dice = LoadedDice;
+    let mut jill = Character::new("jill", Race::Ork);
+    jill.set_attr(Attribute::Willpower, 6).unwrap();
+    jill.set_magic(6);
+    jill.learn_skill("sorcery");
+    jill.improve_skill_by("sorcery", 3);
+    jill.learn_spell("confuse");
+    jill.improve_spell_by("confuse", 3);
+
+    let mut frank = Character::new("frank", Race::Elf);
+    frank.set_attr(Attribute::Willpower, 4).unwrap();
+
+    let grimoire = Grimoire::core();
+    let result = jill.cast_named_at(&dice, &grimoire, "Confuse", &mut frank).unwrap();
+    assert!(result.success);
+    assert_eq!(result.successes, 4);
+    assert_eq!(result.drain_result, None);
+}
+
+#[test]
+fn test_events_reach_a_custom_sink() {
+    let log = Rc::new(EventLog::new());
+    let mut froz = Character::new("froz", Race::Troll);
+    froz.set_sink(log.clone());
+    froz.injure(DamageType::Stun, 10);
+    assert_eq!(log.take(), vec![Event::KnockedOut { name: "froz".to_owned() }]);
+}
+
+fn runner_from_user_input(name: &str, skill: &str) -> Character {
+    let mut c = Character::new(name, Race::Dwarf);
+    c.set_attr(Attribute::Body, 5).unwrap();
+    c.set_magic(6);
+    c.learn_skill(skill);
+    c.improve_skill_by(skill, 2);
+    c.specialize(skill, None, Some("hacking")).unwrap();
+    c.learn_spell("manabolt");
+    c.learn_skill("sorcery");
+    c.improve_skill_by("sorcery", 2);
+    c.learn_spell("oxygenate");
+    c.cast_named(&LoadedDice, &Grimoire::core(), "oxygenate").unwrap();
+    c.injure(DamageType::Stun, 2);
+    c.install(Implant::new(Cyberware::Smartlink, 1, Grade::Alpha).unwrap()).unwrap();
+    c.wear(Armor::new("lined coat", 4, 2));
+    c.earn_karma(12);
+    c.advance(Advance::Knowledge, "seattle gangs").unwrap();
+    c
+}
+
+#[test]
+fn test_json_round_trip() {
+    let name = String::from("Dodger");
+    let before = runner_from_user_input(&name, "computers");
+    let json = serde_json::to_string(&before).unwrap();
+    let after: Character = serde_json::from_str(&json).unwrap();
+    assert_eq!(after.to_string(), before.to_string());
+    assert_eq!(after.name(), "Dodger");
+    assert_eq!(after.skill("computers"), 2);
+    assert_eq!(after.skill_rating("computers", &["hacking"]), 4);
+    assert_eq!(after.spell_force("manabolt"), 1);
+}
+
+#[test]
+fn test_toml_round_trip() {
+    let before = runner_from_user_input("Dodger", "electronics");
+    let text = toml::to_string(&before).unwrap();
+    let after: Character = toml::from_str(&text).unwrap();
+    assert_eq!(after.to_string(), before.to_string());
+    assert_eq!(after.base_skill("electronics"), 3);
+    assert_eq!(after.specialty("electronics"), before.specialty("electronics"));
+    assert_eq!(after.cyberware(), before.cyberware());
+    assert_eq!(after.essence(), 560);
+    assert_eq!(after.armor(), before.armor());
+    assert_eq!(after.sustaining().len(), 1);
+    assert_eq!(after.sustaining(), before.sustaining());
+    assert_eq!(after.karma(), 11);
+    assert_eq!(after.karma_earned(), 12);
+    assert_eq!(after.knowledge("seattle gangs"), 1);
+    assert_eq!(after.ledger(), before.ledger());
+}
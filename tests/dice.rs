@@ -0,0 +1,38 @@
+extern crate shadowfun;
+
+use shadowfun::dice::{Die, Roller, SeededRoller};
+use shadowfun::notation::{Expr, ExprResult};
+
+#[test]
+fn test_same_seed_same_dice() {
+    let a = SeededRoller::with_seed(false, 2050);
+    let b = SeededRoller::with_seed(false, 2050);
+    assert_eq!(a.roll(12, 4), b.roll(12, 4));
+    assert_eq!(a.nd6(5), b.nd6(5));
+}
+
+#[test]
+fn test_roll_result_counts_its_dice() {
+    let roller = SeededRoller::with_seed(false, 77);
+    let result = roller.roll(8, 3);
+    assert_eq!(result.dice.len(), 8);
+    assert_eq!(result.target, 3);
+    let successes = result.dice.iter().filter(|d| d.total() > 3).count() as i32;
+    assert_eq!(result.successes, successes);
+    assert_eq!(result.success, successes > 0);
+}
+
+#[test]
+fn test_notation_through_public_api() {
+    let roller = SeededRoller::with_seed(false, 1);
+    let expr: Expr = "4d6+3".parse().unwrap();
+    match expr.roll(&roller) {
+        ExprResult::Total { dice, modifier, total } => {
+            assert_eq!(dice.len(), 4);
+            assert_eq!(modifier, 3);
+            assert_eq!(total, dice.iter().map(Die::total).sum::<i32>() + 3);
+        },
+        other => panic!("expected a total, got {:?}", other),
+    }
+    assert!("4d6 tn".parse::<Expr>().is_err());
+}